@@ -0,0 +1,71 @@
+//! Measures the time per simulation tick as the number of boids and worker threads grows.
+//!
+//! The compute task pool can only be created once per process, so the benchmark re-runs itself
+//! once per thread count with `BOIDS_BENCH_THREADS` set. Set it yourself to only run one.
+
+use std::{env, process::Command, time::{Duration, Instant}};
+
+use bevy::{core::TaskPoolOptions, prelude::*, time::TimeUpdateStrategy};
+use boids::{headless::run_ticks, prelude::*, simulation_schedule::SimulationSettings};
+
+const BOID_COUNTS: [usize; 5] = [1_000, 5_000, 10_000, 25_000, 50_000];
+const THREAD_COUNTS: [usize; 4] = [1, 2, 4, 8];
+const THREADS_VAR: &str = "BOIDS_BENCH_THREADS";
+// Ticks run before timing, so the flock has formed and the buffers have grown
+const WARMUP_TICKS: u64 = 20;
+const MEASURED_TICKS: u64 = 50;
+
+fn main() {
+    match env::var(THREADS_VAR).ok().and_then(|threads| threads.parse().ok()) {
+        Some(threads) => run(threads),
+        None => {
+            let exe = env::current_exe().expect("benchmark executable path");
+            for threads in THREAD_COUNTS {
+                let status = Command::new(&exe)
+                    .env(THREADS_VAR, threads.to_string())
+                    .status()
+                    .expect("failed to run benchmark");
+                if !status.success() {
+                    eprintln!("benchmark with {} threads failed", threads);
+                }
+            }
+        }
+    }
+}
+
+fn run(threads: usize) {
+    for count in BOID_COUNTS {
+        let mut app = bench_app(count, threads);
+        run_ticks(&mut app, WARMUP_TICKS);
+        let start = Instant::now();
+        run_ticks(&mut app, WARMUP_TICKS + MEASURED_TICKS);
+        let per_tick = start.elapsed() / MEASURED_TICKS as u32;
+        println!("threads: {:>2}  boids: {:>6}  {:>9.3} ms/tick", threads, count, per_tick.as_secs_f64() * 1000.0);
+    }
+}
+
+fn bench_app(count: usize, threads: usize) -> App {
+    let mut manifest = SpeciesManifest::default();
+    for species in manifest.species.iter_mut() {
+        if species.role == Role::Prey {
+            species.count = count;
+        }
+    }
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins.set(TaskPoolPlugin {
+        task_pool_options: TaskPoolOptions::with_num_threads(threads),
+    }))
+    .add_plugins(SimulationPlugin {
+        seed: Some(0),
+        species: Some(manifest),
+        config: None,
+        ..default()
+    });
+
+    let tick_rate = app.world.resource::<SimulationSettings>().tick_rate;
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / tick_rate)));
+    app.finish();
+    app.cleanup();
+    app
+}
@@ -1,6 +1,6 @@
-use bevy::{animation, prelude::*, utils::HashMap};
+use bevy::{prelude::*, utils::HashMap};
 
-use crate::{flock::Boid, utils::get_top_entity};
+use crate::{flock::Boid, obstacle::Obstacle, species::SpeciesManifest, utils::get_top_entity};
 
 #[derive(Resource, Debug, Default)]
 pub struct SimAssets {
@@ -17,18 +17,40 @@ impl Plugin for AssetLoaderPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SimAssets>()
             .add_systems(PreStartup, load_assets)
-            .add_systems(Update, (link_animations, init_animations).chain());
+            .add_systems(Update, (attach_models, link_animations, init_animations).chain())
+            .add_systems(Update, attach_obstacle_meshes);
 
     }
 }   
 
-fn load_assets(mut assets: ResMut<SimAssets>, server: Res<AssetServer>) {
-    for name in &["Fish", "Shark"] {
-        assets.models.insert(name.to_string(), server.load(format!("{}.glb#Scene0", name)));
-        assets.animations.insert(name.to_string(), server.load(format!("{}.glb#Animation0", name)));
+fn load_assets(mut assets: ResMut<SimAssets>, server: Res<AssetServer>, manifest: Res<SpeciesManifest>) {
+    for species in &manifest.species {
+        assets.models.insert(species.name.clone(), server.load(format!("{}#Scene0", species.model)));
+        assets.animations.insert(species.name.clone(), server.load(format!("{}#Animation{}", species.model, species.animation)));
     }
 }
 
+fn attach_models(mut commands: Commands, assets: Res<SimAssets>, boids: Query<(Entity, &Boid), Without<Handle<Scene>>>) {
+    for (entity, boid) in boids.iter() {
+        let model = assets.models.get(&boid.species).unwrap_or_else(|| panic!("Model '{}' should exist", boid.species));
+        commands.entity(entity).insert(model.clone());
+    }
+}
+
+fn attach_obstacle_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    obstacles: Query<(Entity, Ref<Obstacle>), Changed<Obstacle>>,
+) {
+    // also redraws obstacles whose shape was changed
+    for (entity, obstacle) in obstacles.iter() {
+        commands.entity(entity).insert(meshes.add(obstacle.shape().mesh()));
+        if obstacle.is_added() {
+            commands.entity(entity).insert(materials.add(Color::rgb(0.45, 0.4, 0.35).into()));
+        }
+    }
+}
 
 fn link_animations(mut commands: Commands, animation_players: Query<Entity, Added<AnimationPlayer>>, parents: Query<&Parent>) {
     for ani in animation_players.iter() {
@@ -47,7 +69,7 @@ fn init_animations(assets: Res<SimAssets>, mut boids_with_animations: Query<(&Bo
     //     }
     // }
     for (i, (boid, link)) in boids_with_animations.iter_mut().enumerate() {
-        let animation = assets.animations.get(&boid.model).expect(format!("No animation for {}", boid.model).as_str()).clone_weak();
+        let animation = assets.animations.get(&boid.species).unwrap_or_else(|| panic!("No animation for {}", boid.species)).clone_weak();
         if let Ok(mut player) = animation_players.get_mut(link.0) {
             player.play(animation).seek_to((i as f32) * 0.1).repeat();
         } 
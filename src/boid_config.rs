@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{energy::EnergyConfig, escape::EscapePattern, flock_centre::CentreMode, hunting::HuntingStrategy};
+
+// Smallest allowed rule range, which also keeps the BoidMap resolution above zero
+pub const MIN_RANGE: f32 = 1.0;
+// Largest number of neighbours a boid can interact with in a topological neighbourhood
+pub const MAX_TOPOLOGICAL_NEIGHBOURS: usize = 16;
+// Grid cells are at least this fraction of the largest range, so no query visits more than
+// 9 cells along each axis
+const MIN_CELL_FRACTION: f32 = 0.25;
+
+/// Which neighbours a boid interacts with
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Neighbourhood {
+    // Every visible boid within the range of each rule
+    #[default]
+    Metric,
+    // The `k` nearest visible boids, shared by all rules. Separation considers boids of any
+    // species within the separation range, alignment and cohesion those of the same species at
+    // any distance. Neighbours are still only searched for within the largest rule range
+    Topological { k: usize },
+    // As `Topological`, with a different number of neighbours for each rule
+    TopologicalPerRule { separation: usize, alignment: usize, cohesion: usize },
+}
+
+impl Neighbourhood {
+    // Number of neighbours used by the separation, alignment and cohesion rules
+    pub fn counts(&self) -> Option<(usize, usize, usize)> {
+        match *self {
+            Neighbourhood::Metric => None,
+            Neighbourhood::Topological { k } => Some((k, k, k)),
+            Neighbourhood::TopologicalPerRule { separation, alignment, cohesion } => Some((separation, alignment, cohesion)),
+        }
+    }
+}
+
+/// Behaviour parameters of a single species
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoidConfig {
+    pub min_speed: f32,
+    pub max_speed: f32,
+    
+    // View angle in radians
+    pub view_angle: f32,
+    pub neighbourhood: Neighbourhood,
+
+    pub separation_strength: f32,
+    pub separation_range: f32,
+    pub alignment_strength: f32,
+    pub alignment_range: f32,
+    pub cohesion_strength: f32,
+    pub cohesion_range: f32,
+
+    pub flock_centre_strength: f32,
+    pub centre_mode: CentreMode,
+
+    pub predator_strength: f32,
+    pub hunting_strategy: HuntingStrategy,
+    // Steering along the wander heading of a predator with no prey in range, and how fast that heading turns
+    pub wander_strength: f32,
+    pub wander_jitter: f32,
+    // How far predators look for prey
+    pub predator_range: f32,
+    // How far prey see predators, and how hard they steer to escape them
+    pub detection_range: f32,
+    pub predator_avoidance_strength: f32,
+    pub escape_pattern: EscapePattern,
+    // Fraction of a neighbour's panic a prey picks up, below 1 so that panic dies out, and how
+    // much panic fades per second
+    pub panic_transfer: f32,
+    pub panic_decay: f32,
+    // Predators can catch prey this close to them
+    pub capture_radius: f32,
+    // Chance of catching the closest prey within the capture radius on each tick
+    pub capture_probability: f32,
+    // Predators keep this far from other predators, and ignore their pack's alignment and cohesion when solitary
+    pub predator_spacing_range: f32,
+    pub predator_spacing_strength: f32,
+    pub solitary: bool,
+    // Predators straying further than this from where they spawned are pulled back, unless it is 0
+    pub territory_radius: f32,
+    pub territory_strength: f32,
+
+    // How far ahead along their velocity boids look for obstacles
+    pub obstacle_avoidance_distance: f32,
+    pub obstacle_avoidance_strength: f32,
+
+    // Energy drained by swimming, which slows tired boids and weakens their steering. Boids never tire when `None`
+    pub energy: Option<EnergyConfig>,
+}
+
+impl Default for BoidConfig {
+    fn default() -> Self {
+        Self {
+            min_speed: 10.0,
+            max_speed: 30.0,
+            view_angle: f32::to_radians(120.0),
+            neighbourhood: Neighbourhood::default(),
+            separation_strength: 5.0,
+            separation_range: 50.0,
+            alignment_strength: 5.0,
+            alignment_range: 75.0,
+            cohesion_strength: 5.0,
+            cohesion_range: 100.0,
+            flock_centre_strength: 2.0,
+            centre_mode: CentreMode::default(),
+            predator_strength: 30.0,
+            hunting_strategy: HuntingStrategy::default(),
+            wander_strength: 10.0,
+            wander_jitter: 2.0,
+            predator_range: 150.0,
+            detection_range: 150.0,
+            predator_avoidance_strength: 5.0,
+            escape_pattern: EscapePattern::default(),
+            panic_transfer: 0.5,
+            panic_decay: 1.0,
+            capture_radius: 10.0,
+            capture_probability: 0.5,
+            predator_spacing_range: 100.0,
+            predator_spacing_strength: 10.0,
+            solitary: false,
+            territory_radius: 0.0,
+            territory_strength: 30.0,
+            obstacle_avoidance_distance: 80.0,
+            obstacle_avoidance_strength: 60.0,
+            energy: None,
+        }
+    }
+}
+
+impl BoidConfig {
+    /// Clamps the config into a valid state, returning a warning for every value that was changed
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        for (name, value) in [
+            ("Min speed", &mut self.min_speed),
+            ("Max speed", &mut self.max_speed),
+            ("Separation strength", &mut self.separation_strength),
+            ("Alignment strength", &mut self.alignment_strength),
+            ("Cohesion strength", &mut self.cohesion_strength),
+            ("Flock centre strength", &mut self.flock_centre_strength),
+            ("Predator strength", &mut self.predator_strength),
+            ("Wander strength", &mut self.wander_strength),
+            ("Wander jitter", &mut self.wander_jitter),
+            ("Predator avoidance strength", &mut self.predator_avoidance_strength),
+            ("Panic decay", &mut self.panic_decay),
+            ("Obstacle avoidance strength", &mut self.obstacle_avoidance_strength),
+            ("Capture radius", &mut self.capture_radius),
+            ("Predator spacing strength", &mut self.predator_spacing_strength),
+            ("Territory radius", &mut self.territory_radius),
+            ("Territory strength", &mut self.territory_strength),
+        ] {
+            if !value.is_finite() || *value < 0.0 {
+                warnings.push(format!("{} was {}, set to 0", name, value));
+                *value = 0.0;
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.capture_probability) {
+            let clamped = if self.capture_probability > 1.0 { 1.0 } else { 0.0 };
+            warnings.push(format!("Capture probability {} was outside 0..=1, set to {}", self.capture_probability, clamped));
+            self.capture_probability = clamped;
+        }
+
+        if !(0.0..1.0).contains(&self.panic_transfer) {
+            let clamped = if self.panic_transfer >= 1.0 { 0.95 } else { 0.0 };
+            warnings.push(format!("Panic transfer {} was outside 0..1, set to {}", self.panic_transfer, clamped));
+            self.panic_transfer = clamped;
+        }
+
+        if self.min_speed > self.max_speed {
+            warnings.push(format!("Min speed {} was above max speed {}, lowered to match", self.min_speed, self.max_speed));
+            self.min_speed = self.max_speed;
+        }
+
+        // angles between velocities are always in 0..=PI
+        if !(0.0..=std::f32::consts::PI).contains(&self.view_angle) {
+            let clamped = if self.view_angle > 0.0 { std::f32::consts::PI } else { 0.0 };
+            warnings.push(format!("View angle {} was outside 0..=PI, set to {}", self.view_angle, clamped));
+            self.view_angle = clamped;
+        }
+        if self.view_angle == 0.0 {
+            warnings.push("View angle is 0, boids cannot see each other".to_string());
+        }
+
+        let mut counts = match &mut self.neighbourhood {
+            Neighbourhood::Metric => vec![],
+            Neighbourhood::Topological { k } => vec![("Neighbour count", k)],
+            Neighbourhood::TopologicalPerRule { separation, alignment, cohesion } => vec![
+                ("Separation neighbour count", separation),
+                ("Alignment neighbour count", alignment),
+                ("Cohesion neighbour count", cohesion),
+            ],
+        };
+        for (name, value) in counts.iter_mut() {
+            if !(1..=MAX_TOPOLOGICAL_NEIGHBOURS).contains(*value) {
+                let clamped = (**value).clamp(1, MAX_TOPOLOGICAL_NEIGHBOURS);
+                warnings.push(format!("{} was {}, set to {}", name, value, clamped));
+                **value = clamped;
+            }
+        }
+
+        for (name, value) in [
+            ("Separation range", &mut self.separation_range),
+            ("Alignment range", &mut self.alignment_range),
+            ("Cohesion range", &mut self.cohesion_range),
+            ("Predator range", &mut self.predator_range),
+            ("Detection range", &mut self.detection_range),
+            ("Predator spacing range", &mut self.predator_spacing_range),
+            ("Obstacle avoidance distance", &mut self.obstacle_avoidance_distance),
+        ] {
+            // an infinite range would make neighbour queries visit every cell there could be
+            if !value.is_finite() || *value < MIN_RANGE {
+                warnings.push(format!("{} was {}, set to {}", name, value, MIN_RANGE));
+                *value = MIN_RANGE;
+            }
+        }
+
+        if let Some(energy) = &mut self.energy {
+            warnings.extend(energy.validate());
+        }
+
+        warnings
+    }
+}
+
+/// Behaviour parameters for every species, looked up per entity by the rules
+#[derive(Resource, Debug, Clone, Default)]
+pub struct SpeciesConfigs {
+    // Used by any species without its own entry
+    pub default: BoidConfig,
+    pub species: BTreeMap<String, BoidConfig>,
+}
+
+impl SpeciesConfigs {
+    // Replaces the default parameters and those of every species in `other`, keeping the rest
+    pub fn merge(&mut self, other: SpeciesConfigs) {
+        self.default = other.default;
+        self.species.extend(other.species);
+    }
+
+    pub fn get(&self, species: &str) -> &BoidConfig {
+        self.species.get(species).unwrap_or(&self.default)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BoidConfig> {
+        std::iter::once(&self.default).chain(self.species.values())
+    }
+
+    // Largest range of any rule of any species
+    pub fn max_range(&self) -> f32 {
+        self.iter()
+            .map(|config| config.separation_range.max(config.alignment_range).max(config.cohesion_range).max(config.predator_range).max(config.detection_range).max(config.predator_spacing_range))
+            .fold(MIN_RANGE, f32::max)
+    }
+
+    // Smallest range of any rule of any species
+    pub fn min_range(&self) -> f32 {
+        self.iter()
+            .map(|config| config.separation_range.min(config.alignment_range).min(config.cohesion_range).min(config.predator_range).min(config.detection_range).min(config.predator_spacing_range))
+            .fold(f32::MAX, f32::min)
+            .max(MIN_RANGE)
+    }
+
+    /// Side length of a BoidMap cell. Small cells keep short range queries such as separation
+    /// from scanning boids far out of range, while long range queries visit more cells
+    pub fn cell_size(&self) -> f32 {
+        self.min_range().max(self.max_range() * MIN_CELL_FRACTION)
+    }
+
+    /// Validates the parameters of every species, see [`BoidConfig::validate`]
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut warnings: Vec<String> = self.default.validate().into_iter()
+            .map(|warning| format!("Default: {}", warning))
+            .collect();
+        for (name, config) in self.species.iter_mut() {
+            warnings.extend(config.validate().into_iter().map(|warning| format!("{}: {}", name, warning)));
+        }
+
+        // the capture radius is not a rule range, so the BoidMap cells are not sized for it
+        let max_range = self.max_range();
+        let configs = std::iter::once(("Default", &mut self.default))
+            .chain(self.species.iter_mut().map(|(name, config)| (name.as_str(), config)));
+        for (name, config) in configs {
+            if config.capture_radius > max_range {
+                warnings.push(format!("{}: Capture radius {} was above the largest range {}, lowered to match", name, config.capture_radius, max_range));
+                config.capture_radius = max_range;
+            }
+        }
+        warnings
+    }
+}
+
+/// Warnings from every time validation had to correct the config, kept until dismissed
+#[derive(Resource, Debug, Default)]
+pub struct ConfigWarnings(pub Vec<String>);
@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+
+/// Snapshot of a boid taken when the map is rebuilt, so rules can read their neighbours
+/// without looking them up in a query
+#[derive(Debug, Clone, Copy)]
+pub struct BoidEntry {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub flock: usize,
+    pub predator: bool,
+    cell: IVec3,
+}
+
+/// Spatial grid of boids for neighbour queries.
+///
+/// Entries are counting sorted into a hashed table of buckets, one contiguous run of entries per
+/// bucket. All buffers are reused between rebuilds, so once they have grown to fit the population
+/// neither rebuilding nor querying allocates.
+#[derive(Resource, Debug)]
+pub struct BoidMap {
+    // Side length of a grid cell
+    pub resolution: f32,
+    // Sorted by bucket after `rebuild`
+    entries: Vec<BoidEntry>,
+    // Entries in insertion order, waiting for the next rebuild
+    pending: Vec<BoidEntry>,
+    // Entries of bucket `b` are `entries[bucket_starts[b]..bucket_starts[b + 1]]`
+    bucket_starts: Vec<u32>,
+    bucket_cursors: Vec<u32>,
+}
+
+impl Default for BoidMap {
+    fn default() -> Self {
+        Self {
+            resolution: 1.0,
+            entries: Vec::new(),
+            pending: Vec::new(),
+            bucket_starts: vec![0, 0],
+            bucket_cursors: Vec::new(),
+        }
+    }
+}
+
+impl BoidMap {
+    /// Clears the map and sets the cell size for the boids added before the next [`BoidMap::rebuild`]
+    pub fn reset(&mut self, resolution: f32) {
+        self.resolution = resolution;
+        self.pending.clear();
+    }
+
+    pub fn add_boid(&mut self, entity: Entity, position: Vec3, velocity: Vec3, flock: usize, predator: bool) {
+        let cell = self.vec3_to_grid(position);
+        self.pending.push(BoidEntry {
+            entity,
+            position,
+            velocity,
+            flock,
+            predator,
+            cell,
+        });
+    }
+
+    /// Sorts the boids added since the last reset into their buckets
+    pub fn rebuild(&mut self) {
+        // about two buckets per boid keeps collisions between cells rare
+        let buckets = (self.pending.len() * 2).next_power_of_two();
+
+        self.bucket_starts.clear();
+        self.bucket_starts.resize(buckets + 1, 0);
+        for entry in &self.pending {
+            self.bucket_starts[Self::bucket(entry.cell, buckets) + 1] += 1;
+        }
+        for b in 0..buckets {
+            self.bucket_starts[b + 1] += self.bucket_starts[b];
+        }
+
+        self.bucket_cursors.clear();
+        self.bucket_cursors.extend_from_slice(&self.bucket_starts[..buckets]);
+        self.entries.clear();
+        self.entries.extend_from_slice(&self.pending);
+        for entry in &self.pending {
+            let cursor = &mut self.bucket_cursors[Self::bucket(entry.cell, buckets)];
+            self.entries[*cursor as usize] = *entry;
+            *cursor += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[BoidEntry] {
+        &self.entries
+    }
+
+    pub fn vec3_to_grid(&self, position: Vec3) -> IVec3 {
+        (position / self.resolution).floor().as_ivec3()
+    }
+
+    /// Boids in the given cell
+    pub fn cell(&self, cell: IVec3) -> impl Iterator<Item = &BoidEntry> {
+        let bucket = Self::bucket(cell, self.bucket_starts.len() - 1);
+        let start = self.bucket_starts[bucket] as usize;
+        let end = self.bucket_starts[bucket + 1] as usize;
+        // other cells may share the bucket
+        self.entries[start..end].iter().filter(move |entry| entry.cell == cell)
+    }
+
+    /// Boids within `radius` of a position, visiting as many cells as the radius covers
+    pub fn within(&self, position: Vec3, radius: f32) -> impl Iterator<Item = &BoidEntry> {
+        let min = self.vec3_to_grid(position - Vec3::splat(radius));
+        let max = self.vec3_to_grid(position + Vec3::splat(radius));
+        let radius_squared = radius * radius;
+        (min.x..=max.x).flat_map(move |i| {
+            (min.y..=max.y).flat_map(move |j| {
+                (min.z..=max.z).flat_map(move |k| self.cell(IVec3::new(i, j, k)))
+            })
+        })
+        .filter(move |entry| entry.position.distance_squared(position) <= radius_squared)
+    }
+
+    fn bucket(cell: IVec3, buckets: usize) -> usize {
+        let hash = (cell.x as u32).wrapping_mul(73_856_093)
+            ^ (cell.y as u32).wrapping_mul(19_349_663)
+            ^ (cell.z as u32).wrapping_mul(83_492_791);
+        hash as usize & (buckets - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(resolution: f32, positions: &[Vec3]) -> BoidMap {
+        let mut map = BoidMap::default();
+        map.reset(resolution);
+        for (i, position) in positions.iter().enumerate() {
+            map.add_boid(Entity::from_raw(i as u32), *position, Vec3::ZERO, 0, false);
+        }
+        map.rebuild();
+        map
+    }
+
+    fn found(map: &BoidMap, position: Vec3, radius: f32) -> Vec<u32> {
+        let mut found: Vec<u32> = map.within(position, radius).map(|entry| entry.entity.index()).collect();
+        found.sort_unstable();
+        found
+    }
+
+    #[test]
+    fn negative_coordinates_round_down() {
+        let map = build(10.0, &[Vec3::new(-0.5, -9.5, -10.5), Vec3::new(0.5, 9.5, 10.5)]);
+        assert_eq!(map.vec3_to_grid(Vec3::new(-0.5, -9.5, -10.5)), IVec3::new(-1, -1, -2));
+        assert_eq!(map.vec3_to_grid(Vec3::new(0.5, 9.5, 10.5)), IVec3::new(0, 0, 1));
+        assert_eq!(map.cell(IVec3::new(-1, -1, -2)).count(), 1);
+        assert_eq!(found(&map, Vec3::new(-1.0, -9.0, -10.0), 2.0), vec![0]);
+        assert_eq!(found(&map, Vec3::ZERO, 100.0), vec![0, 1]);
+    }
+
+    #[test]
+    fn colliding_cells_are_filtered() {
+        // two boids give four buckets, so most of these cells share a bucket
+        let positions: Vec<Vec3> = (0..2).map(|i| Vec3::new(i as f32 * 50.0 + 0.5, 0.5, 0.5)).collect();
+        let map = build(1.0, &positions);
+        for i in 0..2 {
+            let cell = IVec3::new(i * 50, 0, 0);
+            assert_eq!(map.cell(cell).map(|entry| entry.entity.index()).collect::<Vec<_>>(), vec![i as u32]);
+        }
+        // every cell around the query shares a bucket with one of the boids
+        assert!(found(&map, Vec3::new(25.5, 0.5, 0.5), 3.0).is_empty());
+        assert_eq!(found(&map, Vec3::new(1.0, 0.5, 0.5), 1.0), vec![0]);
+    }
+
+    #[test]
+    fn same_cell_outside_radius_is_filtered() {
+        // same cell as the query, but in the far corner of it
+        let map = build(10.0, &[Vec3::new(1.0, 1.0, 1.0), Vec3::new(9.0, 9.0, 9.0)]);
+        assert_eq!(found(&map, Vec3::new(1.0, 1.0, 1.0), 2.0), vec![0]);
+    }
+
+    #[test]
+    fn radius_larger_than_a_cell() {
+        let positions: Vec<Vec3> = (-5..=5).map(|i| Vec3::new(i as f32, 0.0, 0.0)).collect();
+        let map = build(1.0, &positions);
+        // boids 2..=8 are at -3..=3
+        assert_eq!(found(&map, Vec3::ZERO, 3.5), (2..=8).collect::<Vec<_>>());
+        assert_eq!(found(&map, Vec3::ZERO, 3.0), (2..=8).collect::<Vec<_>>());
+        assert_eq!(found(&map, Vec3::new(0.0, 2.9, 0.0), 3.0), vec![5]);
+    }
+
+    #[test]
+    fn rebuilds_with_a_changing_population() {
+        let mut map = BoidMap::default();
+        for population in [50, 3, 0, 200, 1] {
+            map.reset(2.0);
+            for i in 0..population {
+                let position = Vec3::new((i % 10) as f32 * 3.0 - 15.0, (i / 10) as f32 * -3.0, 0.0);
+                map.add_boid(Entity::from_raw(i), position, Vec3::ZERO, 0, false);
+            }
+            map.rebuild();
+            assert_eq!(map.len(), population as usize);
+            assert_eq!(found(&map, Vec3::ZERO, 1000.0), (0..population).collect::<Vec<_>>());
+            for entry in map.entries() {
+                assert_eq!(found(&map, entry.position, 0.1), vec![entry.entity.index()]);
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid_config::{ConfigWarnings, SpeciesConfigs},
+    flock::{bound_vector, Boid},
+    moveable::{face_direction, move_objects, Velocity},
+    simulation_schedule::{InSimulationSchedule, TransformInterpolation},
+};
+
+// Smallest half extent of a box or radius of a sphere, wrapping divides by the size of the volume
+pub const MIN_BOUNDS_SIZE: f32 = 1.0;
+
+/// Shape of the world, centred on `WorldBounds::centre`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoundsVolume {
+    Box { half_extents: Vec3 },
+    Sphere { radius: f32 },
+}
+
+/// What happens to entities reaching the edge of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoundaryMode {
+    // No boundary, entities can drift away forever
+    #[default]
+    Unbounded,
+    // Leaving through one side enters through the opposite side
+    Wrap,
+    // Reflected back inside, like a ball off a wall
+    Bounce,
+    // Steered back inside once within `soft_margin` of the edge
+    Soft,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct WorldBounds {
+    pub mode: BoundaryMode,
+    pub volume: BoundsVolume,
+    pub centre: Vec3,
+    // Distance from the edge at which soft walls start pushing
+    pub soft_margin: f32,
+    pub soft_strength: f32,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self {
+            mode: BoundaryMode::default(),
+            volume: BoundsVolume::Box { half_extents: Vec3::splat(2000.0) },
+            centre: Vec3::ZERO,
+            soft_margin: 200.0,
+            soft_strength: 50.0,
+        }
+    }
+}
+
+impl WorldBounds {
+    /// Raises the size of the volume to at least `MIN_BOUNDS_SIZE`, returning a warning if it had to
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        match &mut self.volume {
+            BoundsVolume::Box { half_extents } => {
+                if half_extents.is_nan() || half_extents.min_element() < MIN_BOUNDS_SIZE {
+                    let clamped = Vec3::from_array(half_extents.to_array().map(|extent| if extent.is_nan() { MIN_BOUNDS_SIZE } else { extent.max(MIN_BOUNDS_SIZE) }));
+                    warnings.push(format!("World bounds half extents {} were below {}, raised to {}", half_extents, MIN_BOUNDS_SIZE, clamped));
+                    *half_extents = clamped;
+                }
+            }
+            BoundsVolume::Sphere { radius } => {
+                if radius.is_nan() || *radius < MIN_BOUNDS_SIZE {
+                    warnings.push(format!("World bounds radius {} was below {}, raised to {}", radius, MIN_BOUNDS_SIZE, MIN_BOUNDS_SIZE));
+                    *radius = MIN_BOUNDS_SIZE;
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Inward normal of the nearest edge and how far outside of it `position` is, negative when inside
+    pub fn penetration(&self, position: Vec3) -> (Vec3, f32) {
+        let offset = position - self.centre;
+        match self.volume {
+            BoundsVolume::Box { half_extents } => {
+                let depth = offset.abs() - half_extents;
+                let axis = if depth.x >= depth.y && depth.x >= depth.z {
+                    Vec3::X
+                } else if depth.y >= depth.z {
+                    Vec3::Y
+                } else {
+                    Vec3::Z
+                };
+                (axis * -offset.dot(axis).signum(), depth.max_element())
+            }
+            BoundsVolume::Sphere { radius } => (-offset.normalize_or_zero(), offset.length() - radius),
+        }
+    }
+}
+
+pub struct BoundsPlugin;
+
+impl Plugin for BoundsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldBounds>()
+            .init_resource::<ConfigWarnings>()
+            .add_systems(FixedUpdate, validate_world_bounds.in_set(InSimulationSchedule::Perception))
+            .add_systems(FixedUpdate, apply_world_bounds
+                .after(move_objects)
+                .before(face_direction)
+                .in_set(InSimulationSchedule::Movement));
+    }
+}
+
+fn validate_world_bounds(mut bounds: ResMut<WorldBounds>, mut warnings: ResMut<ConfigWarnings>) {
+    if !bounds.is_changed() {
+        return;
+    }
+    let found = bounds.bypass_change_detection().validate();
+    if !found.is_empty() {
+        for warning in &found {
+            warn!("Invalid world bounds: {}", warning);
+        }
+        warnings.0.extend(found);
+    }
+}
+
+// Only boids are kept inside, obstacles are placed by hand and soft walls would push them away forever
+fn apply_world_bounds(
+    bounds: Res<WorldBounds>,
+    configs: Res<SpeciesConfigs>,
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &mut Velocity, &mut TransformInterpolation, &Boid)>,
+) {
+    if bounds.mode == BoundaryMode::Unbounded {
+        return;
+    }
+    query.par_iter_mut().for_each(|(mut transform, mut velocity, mut interpolation, boid)| {
+        let offset = transform.translation - bounds.centre;
+        match (bounds.mode, bounds.volume) {
+            (BoundaryMode::Unbounded, _) => {}
+            (BoundaryMode::Wrap, BoundsVolume::Box { half_extents }) => {
+                if offset.abs().cmpgt(half_extents).any() {
+                    transform.translation = bounds.centre + (offset + half_extents).rem_euclid(half_extents * 2.0) - half_extents;
+                    // jump straight to the other side instead of interpolating across the world
+                    interpolation.previous = *transform;
+                }
+            }
+            (BoundaryMode::Wrap, BoundsVolume::Sphere { radius }) => {
+                let distance = offset.length();
+                if distance > radius {
+                    // re-enter at the opposite point of the sphere, as far inside as it went outside
+                    transform.translation = bounds.centre - offset / distance * (2.0 * radius - distance).max(0.0);
+                    interpolation.previous = *transform;
+                }
+            }
+            (BoundaryMode::Bounce, BoundsVolume::Box { half_extents }) => {
+                for axis in 0..3 {
+                    let limit = half_extents[axis];
+                    if offset[axis].abs() > limit {
+                        let side = offset[axis].signum();
+                        transform.translation[axis] = bounds.centre[axis] + side * (2.0 * limit - offset[axis].abs()).clamp(0.0, limit);
+                        velocity.value[axis] = -side * velocity.value[axis].abs();
+                    }
+                }
+            }
+            (BoundaryMode::Bounce, BoundsVolume::Sphere { radius }) => {
+                let distance = offset.length();
+                if distance > radius {
+                    let normal = offset / distance;
+                    transform.translation = bounds.centre + normal * (2.0 * radius - distance).clamp(0.0, radius);
+                    let outward = velocity.value.dot(normal);
+                    if outward > 0.0 {
+                        velocity.value -= 2.0 * outward * normal;
+                    }
+                }
+            }
+            (BoundaryMode::Soft, _) => {
+                let (inward, penetration) = bounds.penetration(transform.translation);
+                let depth = penetration + bounds.soft_margin;
+                if depth > 0.0 {
+                    // pushes harder the further past the start of the margin
+                    let urgency = depth / bounds.soft_margin.max(f32::EPSILON);
+                    velocity.value += inward * bounds.soft_strength * urgency * time.delta_seconds();
+                    let config = configs.get(&boid.species);
+                    velocity.value = bound_vector(velocity.value, config.min_speed, config.max_speed);
+                }
+            }
+        }
+    });
+}
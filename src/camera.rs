@@ -18,6 +18,12 @@ impl Plugin for CameraPlugin {
     }
 }
 
+/// Point on the horizontal plane through the origin under the cursor, which is where boids start
+/// and where things are placed from the GUI
+pub fn cursor_on_ground(window: &Window, camera: &bevy::render::camera::Camera, camera_transform: &GlobalTransform) -> Option<Vec3> {
+    let ray = camera.viewport_to_world(camera_transform, window.cursor_position()?)?;
+    ray.intersect_plane(Vec3::ZERO, Vec3::Y).map(|distance| ray.get_point(distance))
+}
 
 fn spawn_camera(mut commands: Commands) {
     commands.spawn((
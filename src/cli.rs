@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+/// Options given on the command line
+#[derive(Resource, Debug, Clone)]
+pub struct CliArgs {
+    pub seed: Option<u64>,
+    // Config file to start with
+    pub config: Option<PathBuf>,
+    // Species manifest to use instead of the default one
+    pub species: Option<PathBuf>,
+    // Run the simulation without a window or renderer
+    pub headless: bool,
+    // Number of simulation ticks to run in headless mode
+    pub ticks: u64,
+    // Keep boids on a plane
+    pub two_d: bool,
+}
+
+impl Default for CliArgs {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            config: None,
+            species: None,
+            headless: false,
+            ticks: 1000,
+            two_d: false,
+        }
+    }
+}
+
+impl CliArgs {
+    pub fn parse() -> Self {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Self {
+        let mut cli = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().expect("--seed requires a value");
+                    cli.seed = Some(value.parse().unwrap_or_else(|_| panic!("Invalid seed '{}'", value)));
+                }
+                "--config" => cli.config = Some(args.next().expect("--config requires a path").into()),
+                "--species" => cli.species = Some(args.next().expect("--species requires a path").into()),
+                "--headless" => cli.headless = true,
+                "--2d" => cli.two_d = true,
+                "--ticks" => {
+                    let value = args.next().expect("--ticks requires a value");
+                    cli.ticks = value.parse().unwrap_or_else(|_| panic!("Invalid tick count '{}'", value));
+                }
+                // logging is not set up yet when arguments are parsed
+                _ => eprintln!("Ignoring unknown argument '{}'", arg),
+            }
+        }
+        cli
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    boid_config::{ConfigWarnings, SpeciesConfigs},
+    boid_map::BoidMap,
+    flock::{update_boid_map, Flock, Predator},
+    moveable::Velocity,
+    simulation_schedule::{InSimulationSchedule, SimulationTick},
+};
+
+#[derive(Resource, Debug, Clone)]
+pub struct ClusteringConfig {
+    pub enabled: bool,
+    // Boids closer than this can be in the same cluster
+    pub link_distance: f32,
+    // Largest angle between the headings of two linked boids, in radians
+    pub max_heading_angle: f32,
+    // Only link boids of the same species
+    pub same_species_only: bool,
+    // Clusters smaller than this are not reported
+    pub min_size: usize,
+}
+
+impl Default for ClusteringConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            link_distance: 60.0,
+            max_heading_angle: f32::to_radians(60.0),
+            same_species_only: true,
+            min_size: 3,
+        }
+    }
+}
+
+impl ClusteringConfig {
+    /// Lowers the link distance to at most `max_range`, the largest rule range the BoidMap cells
+    /// are sized for, returning a warning if it had to
+    pub fn validate(&mut self, max_range: f32) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.link_distance.is_nan() || self.link_distance > max_range {
+            warnings.push(format!("Link distance {} was above the largest range {}, lowered to match", self.link_distance, max_range));
+            self.link_distance = max_range;
+        }
+        warnings
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterInfo {
+    pub id: u64,
+    pub size: usize,
+    pub centroid: Vec3,
+    // Tick the cluster was first seen
+    pub born: u64,
+}
+
+impl ClusterInfo {
+    pub fn lifetime(&self, tick: u64) -> u64 {
+        tick - self.born
+    }
+}
+
+/// Emergent flocks found by clustering boids on proximity and heading.
+///
+/// Cluster ids are kept stable between ticks: a cluster takes over the id of the previous
+/// cluster most of its members belonged to, so a school keeps its id as it moves and the
+/// larger half keeps it when the school splits.
+#[derive(Resource, Debug, Default)]
+pub struct Clusters {
+    // Largest first
+    pub clusters: Vec<ClusterInfo>,
+    membership: HashMap<Entity, u64>,
+    next_id: u64,
+}
+
+impl Clusters {
+    pub fn cluster_of(&self, entity: Entity) -> Option<u64> {
+        self.membership.get(&entity).copied()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&ClusterInfo> {
+        self.clusters.iter().find(|cluster| cluster.id == id)
+    }
+}
+
+pub struct ClusteringPlugin;
+
+impl Plugin for ClusteringPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ClusteringConfig>()
+            .init_resource::<Clusters>()
+            .init_resource::<ConfigWarnings>()
+            .add_systems(FixedUpdate, (validate_clustering_config, detect_clusters)
+                .chain()
+                .after(update_boid_map)
+                .in_set(InSimulationSchedule::Perception));
+    }
+}
+
+fn validate_clustering_config(
+    mut config: ResMut<ClusteringConfig>,
+    configs: Res<SpeciesConfigs>,
+    mut warnings: ResMut<ConfigWarnings>,
+) {
+    if !config.is_changed() && !configs.is_changed() {
+        return;
+    }
+    let found = config.bypass_change_detection().validate(configs.max_range());
+    if !found.is_empty() {
+        for warning in &found {
+            warn!("Invalid clustering config: {}", warning);
+        }
+        warnings.0.extend(found);
+    }
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        // path halving keeps the trees shallow
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+fn detect_clusters(
+    config: Res<ClusteringConfig>,
+    map: Res<BoidMap>,
+    tick: Res<SimulationTick>,
+    mut clusters: ResMut<Clusters>,
+    boids: Query<(Entity, &Transform, &Velocity, &Flock), Without<Predator>>,
+    mut indices: Local<HashMap<Entity, usize>>,
+) {
+    if !config.enabled {
+        clusters.clusters.clear();
+        clusters.membership.clear();
+        return;
+    }
+
+    indices.clear();
+    for (i, (entity, ..)) in boids.iter().enumerate() {
+        indices.insert(entity, i);
+    }
+
+    // union-find over every pair of linked neighbours
+    let mut parents: Vec<usize> = (0..indices.len()).collect();
+    let cos_heading = config.max_heading_angle.cos();
+    for (i, (_, transform1, velocity1, flock1)) in boids.iter().enumerate() {
+        let heading1 = velocity1.value.normalize_or_zero();
+        for other in map.within(transform1.translation, config.link_distance) {
+            let Some(&j) = indices.get(&other.entity) else {
+                continue;
+            };
+            // every pair is seen from both sides, only link it once
+            if j <= i {
+                continue;
+            }
+            if config.same_species_only && flock1.identity != other.flock {
+                continue;
+            }
+            if heading1.dot(other.velocity.normalize_or_zero()) < cos_heading {
+                continue;
+            }
+            let (root1, root2) = (find(&mut parents, i), find(&mut parents, j));
+            if root1 != root2 {
+                parents[root1.max(root2)] = root1.min(root2);
+            }
+        }
+    }
+
+    // group members by root
+    let mut groups: HashMap<usize, Vec<(Entity, Vec3)>> = HashMap::new();
+    for (i, (entity, transform, ..)) in boids.iter().enumerate() {
+        let root = find(&mut parents, i);
+        groups.entry(root).or_default().push((entity, transform.translation));
+    }
+    let mut groups: Vec<Vec<(Entity, Vec3)>> = groups.into_values()
+        .filter(|members| members.len() >= config.min_size.max(1))
+        .collect();
+    // largest clusters pick their ids first, ties broken by entity so the result is reproducible
+    groups.sort_by_key(|members| (std::cmp::Reverse(members.len()), members.iter().map(|(e, _)| *e).min()));
+
+    let previous_born: HashMap<u64, u64> = clusters.clusters.iter().map(|cluster| (cluster.id, cluster.born)).collect();
+    let mut new_clusters = Vec::with_capacity(groups.len());
+    let mut new_membership = HashMap::with_capacity(indices.len());
+    for members in groups {
+        // the previous id held by most members, unless a larger cluster has already taken it
+        let mut votes: HashMap<u64, usize> = HashMap::new();
+        for (entity, _) in &members {
+            if let Some(id) = clusters.membership.get(entity) {
+                *votes.entry(*id).or_default() += 1;
+            }
+        }
+        let mut candidates: Vec<(u64, usize)> = votes.into_iter().collect();
+        candidates.sort_by_key(|(id, count)| (std::cmp::Reverse(*count), *id));
+        let inherited = candidates.into_iter()
+            .map(|(id, _)| id)
+            .find(|id| !new_clusters.iter().any(|cluster: &ClusterInfo| cluster.id == *id));
+
+        let (id, born) = match inherited {
+            Some(id) => (id, previous_born.get(&id).copied().unwrap_or(tick.0)),
+            None => {
+                clusters.next_id += 1;
+                (clusters.next_id, tick.0)
+            }
+        };
+        let centroid = members.iter().map(|(_, position)| *position).sum::<Vec3>() / members.len() as f32;
+        for (entity, _) in &members {
+            new_membership.insert(*entity, id);
+        }
+        new_clusters.push(ClusterInfo {
+            id,
+            size: members.len(),
+            centroid,
+            born,
+        });
+    }
+
+    clusters.clusters = new_clusters;
+    clusters.membership = new_membership;
+}
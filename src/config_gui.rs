@@ -1,7 +1,9 @@
+use std::collections::BTreeSet;
+
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts};
 
-use crate::flock::BoidConfig;
+use crate::{bounds::{BoundaryMode, BoundsVolume, WorldBounds, MIN_BOUNDS_SIZE}, boid_config::{BoidConfig, ConfigWarnings, Neighbourhood, SpeciesConfigs, MAX_TOPOLOGICAL_NEIGHBOURS, MIN_RANGE}, config_watcher::WatchedConfig, energy::EnergyConfig, escape::EscapePattern, flock::Boid, flock_centre::CentreMode, hunting::HuntingStrategy, moveable::Dimensions, population::RespawnBoids, presets::{ConfigFile, Presets, PRESETS_DIR}, rng::SimRng, simulation_schedule::SimulationSettings};
 
 pub struct ConfigGuiPlugin;
 
@@ -11,26 +13,348 @@ impl Plugin for ConfigGuiPlugin {
     }
 }
 
+struct PresetsGuiState {
+    selected_preset: usize,
+    path: String,
+    status: String,
+}
+
+impl Default for PresetsGuiState {
+    fn default() -> Self {
+        Self {
+            selected_preset: 0,
+            path: format!("{}/custom.ron", PRESETS_DIR),
+            status: String::new(),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn setup_config_egui(
     mut contexts: EguiContexts,
-    mut boid_config: ResMut<BoidConfig>,
+    mut species_configs: ResMut<SpeciesConfigs>,
+    mut simulation_settings: ResMut<SimulationSettings>,
+    rng: Res<SimRng>,
+    presets: Res<Presets>,
+    mut presets_state: Local<PresetsGuiState>,
+    mut selected_species: Local<Option<String>>,
+    mut watched_config: ResMut<WatchedConfig>,
+    mut config_warnings: ResMut<ConfigWarnings>,
+    mut world_bounds: ResMut<WorldBounds>,
+    mut dimensions: ResMut<Dimensions>,
+    mut respawn_events: EventWriter<RespawnBoids>,
+    boids: Query<&Boid>,
 ) {
     egui::Window::new("Boid Configuration").show(contexts.ctx_mut(), |ui| {
-        ui.add(egui::Slider::new(&mut boid_config.max_speed, 0.0..=100.0).text("Max Speed"));
-        ui.add(egui::Slider::new(&mut boid_config.min_speed, 0.0..=100.0).text("Min Speed"));
-
-        ui.add(egui::Slider::new(&mut boid_config.view_angle, 0.0..=std::f32::consts::PI).text("View Angle"));
-
-        ui.add(egui::Slider::new(&mut boid_config.separation_strength, 0.0..=20.0).text("Separation Strength"));
-        ui.add(egui::Slider::new(&mut boid_config.separation_range, 0.0..=200.0).text("Separation Range"));
-        ui.add(egui::Slider::new(&mut boid_config.alignment_strength, 0.0..=20.0).text("Alignment Strength"));
-        ui.add(egui::Slider::new(&mut boid_config.alignment_range, 0.0..=200.0).text("Alignment Range"));
-        ui.add(egui::Slider::new(&mut boid_config.cohesion_strength, 0.0..=20.0).text("Cohesion Strength"));
-        ui.add(egui::Slider::new(&mut boid_config.cohesion_range, 0.0..=200.0).text("Cohesion Range"));
-
-        ui.add(egui::Slider::new(&mut boid_config.flock_centre_strength, 0.0..=20.0).text("Flock Centre Strength"));
-                
-        ui.add(egui::Slider::new(&mut boid_config.predator_strength, 0.0..=50.0).text("Predator Strength"));
-        ui.add(egui::Slider::new(&mut boid_config.predator_avoidance_strength, 0.0..=50.0).text("Predator Avoidance Strength"));
+        ui.label(format!("Seed: {}", rng.seed()));
+        presets_ui(ui, &mut presets_state, &presets, &mut species_configs, &mut watched_config, rng.seed());
+        if !config_warnings.0.is_empty() {
+            for warning in &config_warnings.0 {
+                ui.colored_label(egui::Color32::YELLOW, warning);
+            }
+            if ui.button("Dismiss").clicked() {
+                config_warnings.0.clear();
+            }
+        }
+        ui.add(egui::Slider::new(&mut simulation_settings.tick_rate, 1.0..=240.0).text("Tick Rate"));
+        ui.checkbox(&mut simulation_settings.interpolate, "Interpolate Transforms");
+        ui.horizontal(|ui| {
+            let mut selected = *dimensions;
+            ui.selectable_value(&mut selected, Dimensions::Two, "2D");
+            ui.selectable_value(&mut selected, Dimensions::Three, "3D");
+            // boids are spawned differently in each mode, so start over
+            if selected != *dimensions {
+                *dimensions = selected;
+                respawn_events.send(RespawnBoids);
+            }
+            if ui.button("Respawn").clicked() {
+                respawn_events.send(RespawnBoids);
+            }
+        });
+        ui.collapsing("World Bounds", |ui| world_bounds_ui(ui, &mut world_bounds));
+
+        // One tab for the default parameters, and one for every species in the scene or with its own parameters
+        let mut species: BTreeSet<String> = species_configs.species.keys().cloned().collect();
+        for boid in boids.iter() {
+            if !species.contains(&boid.species) {
+                species.insert(boid.species.clone());
+            }
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut *selected_species, None, "Default");
+            for name in species.iter() {
+                ui.selectable_value(&mut *selected_species, Some(name.clone()), name);
+            }
+        });
+
+        match selected_species.clone() {
+            None => boid_config_ui(ui, &mut species_configs.default),
+            Some(name) => {
+                if species_configs.species.contains_key(&name) {
+                    if ui.button("Use Default Parameters").clicked() {
+                        species_configs.species.remove(&name);
+                    } else if let Some(config) = species_configs.species.get_mut(&name) {
+                        boid_config_ui(ui, config);
+                    }
+                } else {
+                    ui.label(format!("{} uses the default parameters", name));
+                    if ui.button("Customise").clicked() {
+                        let config = species_configs.default.clone();
+                        species_configs.species.insert(name, config);
+                    }
+                }
+            }
+        }
     });
-}
\ No newline at end of file
+}
+
+fn boid_config_ui(ui: &mut egui::Ui, boid_config: &mut BoidConfig) {
+    ui.add(egui::Slider::new(&mut boid_config.max_speed, 0.0..=100.0).text("Max Speed"));
+    let max_speed = boid_config.max_speed;
+    ui.add(egui::Slider::new(&mut boid_config.min_speed, 0.0..=max_speed).text("Min Speed"));
+
+    ui.add(egui::Slider::new(&mut boid_config.view_angle, 0.0..=std::f32::consts::PI).text("View Angle"));
+    neighbourhood_ui(ui, &mut boid_config.neighbourhood);
+
+    ui.add(egui::Slider::new(&mut boid_config.separation_strength, 0.0..=20.0).text("Separation Strength"));
+    ui.add(egui::Slider::new(&mut boid_config.separation_range, MIN_RANGE..=200.0).text("Separation Range"));
+    ui.add(egui::Slider::new(&mut boid_config.alignment_strength, 0.0..=20.0).text("Alignment Strength"));
+    ui.add(egui::Slider::new(&mut boid_config.alignment_range, MIN_RANGE..=200.0).text("Alignment Range"));
+    ui.add(egui::Slider::new(&mut boid_config.cohesion_strength, 0.0..=20.0).text("Cohesion Strength"));
+    ui.add(egui::Slider::new(&mut boid_config.cohesion_range, MIN_RANGE..=200.0).text("Cohesion Range"));
+
+    ui.add(egui::Slider::new(&mut boid_config.flock_centre_strength, 0.0..=20.0).text("Flock Centre Strength"));
+    centre_mode_ui(ui, &mut boid_config.centre_mode);
+
+    ui.add(egui::Slider::new(&mut boid_config.predator_strength, 0.0..=50.0).text("Predator Strength"));
+    egui::ComboBox::from_label("Hunting Strategy")
+        .selected_text(format!("{:?}", boid_config.hunting_strategy))
+        .show_ui(ui, |ui| {
+            for strategy in [HuntingStrategy::Nearest, HuntingStrategy::Centroid, HuntingStrategy::Isolated, HuntingStrategy::Intercept, HuntingStrategy::Pack] {
+                ui.selectable_value(&mut boid_config.hunting_strategy, strategy, format!("{:?}", strategy));
+            }
+        });
+    ui.add(egui::Slider::new(&mut boid_config.wander_strength, 0.0..=50.0).text("Wander Strength"));
+    ui.add(egui::Slider::new(&mut boid_config.wander_jitter, 0.0..=10.0).text("Wander Jitter"));
+    ui.add(egui::Slider::new(&mut boid_config.predator_range, MIN_RANGE..=400.0).text("Predator Range"));
+    ui.add(egui::Slider::new(&mut boid_config.detection_range, MIN_RANGE..=400.0).text("Detection Range"));
+    ui.add(egui::Slider::new(&mut boid_config.predator_avoidance_strength, 0.0..=50.0).text("Predator Avoidance Strength"));
+    egui::ComboBox::from_label("Escape Pattern")
+        .selected_text(format!("{:?}", boid_config.escape_pattern))
+        .show_ui(ui, |ui| {
+            for pattern in [EscapePattern::Flee, EscapePattern::FlashExpansion, EscapePattern::Fountain, EscapePattern::Herd] {
+                ui.selectable_value(&mut boid_config.escape_pattern, pattern, format!("{:?}", pattern));
+            }
+        });
+    ui.add(egui::Slider::new(&mut boid_config.panic_transfer, 0.0..=0.95).text("Panic Transfer"));
+    ui.add(egui::Slider::new(&mut boid_config.panic_decay, 0.0..=5.0).text("Panic Decay"));
+    ui.add(egui::Slider::new(&mut boid_config.capture_radius, 0.0..=100.0).text("Capture Radius"));
+    ui.add(egui::Slider::new(&mut boid_config.capture_probability, 0.0..=1.0).text("Capture Probability"));
+    ui.add(egui::Slider::new(&mut boid_config.predator_spacing_range, MIN_RANGE..=400.0).text("Predator Spacing Range"));
+    ui.add(egui::Slider::new(&mut boid_config.predator_spacing_strength, 0.0..=50.0).text("Predator Spacing Strength"));
+    ui.checkbox(&mut boid_config.solitary, "Solitary");
+    ui.add(egui::Slider::new(&mut boid_config.territory_radius, 0.0..=2000.0).text("Territory Radius"));
+    ui.add(egui::Slider::new(&mut boid_config.territory_strength, 0.0..=50.0).text("Territory Strength"));
+
+    ui.add(egui::Slider::new(&mut boid_config.obstacle_avoidance_distance, MIN_RANGE..=300.0).text("Obstacle Avoidance Distance"));
+    ui.add(egui::Slider::new(&mut boid_config.obstacle_avoidance_strength, 0.0..=200.0).text("Obstacle Avoidance Strength"));
+
+    let mut has_energy = boid_config.energy.is_some();
+    if ui.checkbox(&mut has_energy, "Energy").changed() {
+        boid_config.energy = has_energy.then(EnergyConfig::default);
+    }
+    if let Some(energy) = &mut boid_config.energy {
+        ui.add(egui::Slider::new(&mut energy.speed_cost, 0.0..=0.5).text("Speed Cost"));
+        ui.add(egui::Slider::new(&mut energy.acceleration_cost, 0.0..=0.5).text("Acceleration Cost"));
+        ui.add(egui::Slider::new(&mut energy.rest_recovery, 0.0..=0.5).text("Rest Recovery"));
+        ui.add(egui::Slider::new(&mut energy.feeding_gain, 0.0..=1.0).text("Feeding Gain"));
+        ui.add(egui::Slider::new(&mut energy.min_performance, 0.0..=1.0).text("Min Performance"));
+    }
+}
+
+fn world_bounds_ui(ui: &mut egui::Ui, bounds: &mut WorldBounds) {
+    egui::ComboBox::from_label("Boundary")
+        .selected_text(format!("{:?}", bounds.mode))
+        .show_ui(ui, |ui| {
+            for mode in [BoundaryMode::Unbounded, BoundaryMode::Wrap, BoundaryMode::Bounce, BoundaryMode::Soft] {
+                ui.selectable_value(&mut bounds.mode, mode, format!("{:?}", mode));
+            }
+        });
+    if bounds.mode == BoundaryMode::Unbounded {
+        return;
+    }
+    let label = match bounds.volume {
+        BoundsVolume::Box { .. } => "Box",
+        BoundsVolume::Sphere { .. } => "Sphere",
+    };
+    egui::ComboBox::from_label("Volume")
+        .selected_text(label)
+        .show_ui(ui, |ui| {
+            let options = [
+                ("Box", BoundsVolume::Box { half_extents: Vec3::splat(2000.0) }),
+                ("Sphere", BoundsVolume::Sphere { radius: 2000.0 }),
+            ];
+            for (name, option) in options {
+                if ui.selectable_label(name == label, name).clicked() && name != label {
+                    bounds.volume = option;
+                }
+            }
+        });
+    match &mut bounds.volume {
+        BoundsVolume::Box { half_extents } => {
+            vec3_ui(ui, half_extents, "Half Extents");
+            *half_extents = half_extents.max(Vec3::splat(MIN_BOUNDS_SIZE));
+        }
+        BoundsVolume::Sphere { radius } => {
+            ui.add(egui::Slider::new(radius, 100.0..=5000.0).text("Radius"));
+        }
+    }
+    vec3_ui(ui, &mut bounds.centre, "Centre");
+    if bounds.mode == BoundaryMode::Soft {
+        ui.add(egui::Slider::new(&mut bounds.soft_margin, 1.0..=1000.0).text("Soft Margin"));
+        ui.add(egui::Slider::new(&mut bounds.soft_strength, 0.0..=200.0).text("Soft Strength"));
+    }
+}
+
+fn neighbourhood_ui(ui: &mut egui::Ui, neighbourhood: &mut Neighbourhood) {
+    let label = match neighbourhood {
+        Neighbourhood::Metric => "Metric",
+        Neighbourhood::Topological { .. } => "Topological",
+        Neighbourhood::TopologicalPerRule { .. } => "Topological Per Rule",
+    };
+    egui::ComboBox::from_label("Neighbourhood")
+        .selected_text(label)
+        .show_ui(ui, |ui| {
+            let options = [
+                ("Metric", Neighbourhood::Metric),
+                ("Topological", Neighbourhood::Topological { k: 7 }),
+                ("Topological Per Rule", Neighbourhood::TopologicalPerRule { separation: 7, alignment: 7, cohesion: 7 }),
+            ];
+            for (name, option) in options {
+                if ui.selectable_label(name == label, name).clicked() && name != label {
+                    *neighbourhood = option;
+                }
+            }
+        });
+    let counts = 1..=MAX_TOPOLOGICAL_NEIGHBOURS;
+    match neighbourhood {
+        Neighbourhood::Metric => {}
+        Neighbourhood::Topological { k } => {
+            ui.add(egui::Slider::new(k, counts).text("Neighbours"));
+        }
+        Neighbourhood::TopologicalPerRule { separation, alignment, cohesion } => {
+            ui.add(egui::Slider::new(separation, counts.clone()).text("Separation Neighbours"));
+            ui.add(egui::Slider::new(alignment, counts.clone()).text("Alignment Neighbours"));
+            ui.add(egui::Slider::new(cohesion, counts).text("Cohesion Neighbours"));
+        }
+    }
+}
+
+fn centre_mode_ui(ui: &mut egui::Ui, centre_mode: &mut CentreMode) {
+    let label = match centre_mode {
+        CentreMode::OwnCentroid => "Own Centroid",
+        CentreMode::HomeAnchor { .. } => "Home Anchor",
+        CentreMode::Waypoint { .. } => "Moving Waypoint",
+    };
+    egui::ComboBox::from_label("Flock Centre")
+        .selected_text(label)
+        .show_ui(ui, |ui| {
+            let options = [
+                ("Own Centroid", CentreMode::OwnCentroid),
+                ("Home Anchor", CentreMode::default()),
+                ("Moving Waypoint", CentreMode::Waypoint { centre: Vec3::ZERO, radius: 500.0, period: 60.0 }),
+            ];
+            for (name, option) in options {
+                if ui.selectable_label(name == label, name).clicked() && name != label {
+                    *centre_mode = option;
+                }
+            }
+        });
+    match centre_mode {
+        CentreMode::OwnCentroid => {}
+        CentreMode::HomeAnchor { position } => vec3_ui(ui, position, "Anchor"),
+        CentreMode::Waypoint { centre, radius, period } => {
+            vec3_ui(ui, centre, "Circle Centre");
+            ui.add(egui::Slider::new(radius, 0.0..=2000.0).text("Circle Radius"));
+            ui.add(egui::Slider::new(period, 1.0..=300.0).text("Lap Time"));
+        }
+    }
+}
+
+pub fn vec3_ui(ui: &mut egui::Ui, value: &mut Vec3, label: &str) {
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut value.x).prefix("x: "));
+        ui.add(egui::DragValue::new(&mut value.y).prefix("y: "));
+        ui.add(egui::DragValue::new(&mut value.z).prefix("z: "));
+        ui.label(label);
+    });
+}
+
+fn presets_ui(
+    ui: &mut egui::Ui,
+    state: &mut PresetsGuiState,
+    presets: &Presets,
+    species_configs: &mut SpeciesConfigs,
+    watched_config: &mut WatchedConfig,
+    seed: u64,
+) {
+    let preset_name = |i: usize| presets.files.get(i)
+        .and_then(|path| path.file_stem())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Preset")
+            .selected_text(preset_name(state.selected_preset))
+            .show_ui(ui, |ui| {
+                for i in 0..presets.files.len() {
+                    ui.selectable_value(&mut state.selected_preset, i, preset_name(i));
+                }
+            });
+        if ui.button("Apply").clicked() {
+            if let Some(path) = presets.files.get(state.selected_preset) {
+                state.path = path.to_string_lossy().to_string();
+                state.status = load_into(&state.path, species_configs);
+                watched_config.watch(&state.path);
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut state.path);
+        if ui.button("Save").clicked() {
+            let file = ConfigFile::from_configs(species_configs, Some(seed));
+            state.status = match file.save(&state.path) {
+                Ok(()) => {
+                    watched_config.watch(&state.path);
+                    format!("Saved {}", state.path)
+                }
+                Err(e) => format!("Failed to save {}: {}", state.path, e),
+            };
+        }
+        if ui.button("Load").clicked() {
+            state.status = load_into(&state.path, species_configs);
+            watched_config.watch(&state.path);
+        }
+    });
+    if !state.status.is_empty() {
+        ui.label(&state.status);
+    }
+    if let Some(path) = watched_config.path() {
+        ui.label(format!("Watching {}", path.display()));
+    }
+    if let Some(error) = &watched_config.error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+}
+
+// Returns the status message to show
+fn load_into(path: &str, species_configs: &mut SpeciesConfigs) -> String {
+    match ConfigFile::load(path) {
+        Ok(file) => {
+            species_configs.merge(file.configs());
+            format!("Loaded {}", path)
+        }
+        Err(e) => format!("Failed to load {}: {}", path, e),
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+
+use crate::{boid_config::SpeciesConfigs, presets::ConfigFile};
+
+const ASSETS_DIR: &str = "assets";
+
+/// A config file loaded through the asset server, so edits on disk are picked up while running.
+///
+/// Parse errors are kept in the asset rather than failing the load, so they can be shown in the GUI.
+#[derive(Asset, TypePath, Debug)]
+pub struct ConfigAsset(pub Result<ConfigFile, String>);
+
+#[derive(Default)]
+struct ConfigAssetLoader;
+
+impl AssetLoader for ConfigAssetLoader {
+    type Asset = ConfigAsset;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<ConfigAsset, Self::Error>> {
+        Box::pin(async move {
+            let mut contents = String::new();
+            reader.read_to_string(&mut contents).await?;
+            Ok(ConfigAsset(ConfigFile::parse(&contents).map_err(|e| e.to_string())))
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// The config file currently being watched, applied to [`SpeciesConfigs`] whenever it changes on disk
+#[derive(Resource, Debug, Default)]
+pub struct WatchedConfig {
+    path: Option<PathBuf>,
+    handle: Option<Handle<ConfigAsset>>,
+    pub error: Option<String>,
+}
+
+impl WatchedConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let mut watched = Self::default();
+        watched.watch(path);
+        watched
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    // Start watching a file, replacing the previous one
+    pub fn watch(&mut self, path: impl Into<PathBuf>) {
+        self.path = Some(path.into());
+        self.handle = None;
+        self.error = None;
+    }
+}
+
+pub struct ConfigWatcherPlugin;
+
+impl Plugin for ConfigWatcherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ConfigAsset>()
+            .init_asset_loader::<ConfigAssetLoader>()
+            .init_resource::<WatchedConfig>()
+            .add_systems(Update, (load_watched_config, apply_watched_config).chain());
+    }
+}
+
+fn load_watched_config(mut watched: ResMut<WatchedConfig>, server: Res<AssetServer>) {
+    if watched.handle.is_some() {
+        return;
+    }
+    let Some(path) = watched.path.clone() else {
+        return;
+    };
+    // The asset server only watches files inside the assets folder
+    match asset_path(&path) {
+        Some(asset_path) => watched.handle = Some(server.load(asset_path)),
+        None => {
+            watched.path = None;
+            watched.error = Some(format!("{} is outside the assets folder and will not be reloaded", path.display()));
+        }
+    }
+}
+
+fn apply_watched_config(
+    mut events: EventReader<AssetEvent<ConfigAsset>>,
+    mut watched: ResMut<WatchedConfig>,
+    configs: Res<Assets<ConfigAsset>>,
+    mut species_configs: ResMut<SpeciesConfigs>,
+) {
+    for event in events.read() {
+        let (AssetEvent::Added { id } | AssetEvent::Modified { id }) = event else {
+            continue;
+        };
+        if watched.handle.as_ref().map(|handle| handle.id()) != Some(*id) {
+            continue;
+        }
+        let Some(ConfigAsset(result)) = configs.get(*id) else {
+            continue;
+        };
+        match result {
+            Ok(file) => {
+                species_configs.merge(file.configs());
+                watched.error = None;
+                info!("Applied config from {:?}", watched.path);
+            }
+            Err(e) => watched.error = Some(e.clone()),
+        }
+    }
+}
+
+// Converts a path relative to the working directory into one relative to the assets folder
+fn asset_path(path: &Path) -> Option<PathBuf> {
+    let assets = Path::new(ASSETS_DIR).canonicalize().ok()?;
+    let path = path.canonicalize().ok()?;
+    path.strip_prefix(assets).ok().map(Path::to_path_buf)
+}
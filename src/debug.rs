@@ -10,16 +10,18 @@ pub struct DebugShape {
 }
 
 
-use crate::flock::{Boid, Flock};
+use crate::{bounds::{BoundaryMode, BoundsVolume, WorldBounds}, flock::{Boid, Flock}};
 pub struct DebugPlugin;
 
 impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
         // app.add_systems(PostUpdate, log_flock);
-        app.add_systems(Update, (despawn_unlinked_entities, update_debug_shapes).chain());
+        app.add_systems(Update, (despawn_unlinked_entities, update_debug_shapes).chain())
+            .add_systems(Update, draw_world_bounds);
     }
 }
 
+#[allow(dead_code)]
 fn log_flock(boids: Query<(Entity, &Transform, &Flock), With<Boid>>) {
     for (e, t, f) in boids.iter() {
         println!("Boid: {:?}, Position: {:?}, Flock: {:?}", e, t.translation, f);
@@ -47,4 +49,19 @@ fn update_debug_shapes(
             t.translation = linked_translation.translation;
         }
     }
-}
\ No newline at end of file
+}
+
+fn draw_world_bounds(bounds: Res<WorldBounds>, mut gizmos: Gizmos) {
+    if bounds.mode == BoundaryMode::Unbounded {
+        return;
+    }
+    let color = Color::rgba(0.6, 0.8, 1.0, 0.5);
+    match bounds.volume {
+        BoundsVolume::Box { half_extents } => {
+            gizmos.cuboid(Transform::from_translation(bounds.centre).with_scale(half_extents * 2.0), color);
+        }
+        BoundsVolume::Sphere { radius } => {
+            gizmos.sphere(bounds.centre, Quat::IDENTITY, radius, color).circle_segments(64);
+        }
+    }
+}
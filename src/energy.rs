@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boid_config::SpeciesConfigs,
+    flock::{bound_vector, Boid},
+    moveable::Velocity,
+    obstacle::avoid_obstacles,
+    predation::{capture_prey, count_captures, PreyCaptured},
+    simulation_schedule::InSimulationSchedule,
+};
+
+/// Energy model of a species. Rates are per second, with energy going from 0 (exhausted) to 1 (rested)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyConfig {
+    // Drain at max speed, growing with the square of the speed
+    pub speed_cost: f32,
+    // Drain for changing velocity by max speed in one second
+    pub acceleration_cost: f32,
+    // Recovery when still, falling to nothing at max speed
+    pub rest_recovery: f32,
+    // Gained by a predator for every prey it catches
+    pub feeding_gain: f32,
+    // Fraction of max speed and rule strengths left when exhausted
+    pub min_performance: f32,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            speed_cost: 0.05,
+            acceleration_cost: 0.02,
+            rest_recovery: 0.05,
+            feeding_gain: 0.5,
+            min_performance: 0.3,
+        }
+    }
+}
+
+impl EnergyConfig {
+    /// Clamps the config into a valid state, returning a warning for every value that was changed
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (name, value) in [
+            ("Speed cost", &mut self.speed_cost),
+            ("Acceleration cost", &mut self.acceleration_cost),
+            ("Rest recovery", &mut self.rest_recovery),
+            ("Feeding gain", &mut self.feeding_gain),
+        ] {
+            if !value.is_finite() || *value < 0.0 {
+                warnings.push(format!("{} was {}, set to 0", name, value));
+                *value = 0.0;
+            }
+        }
+        if !(0.0..=1.0).contains(&self.min_performance) {
+            let clamped = if self.min_performance > 1.0 { 1.0 } else { 0.0 };
+            warnings.push(format!("Min performance {} was outside 0..=1, set to {}", self.min_performance, clamped));
+            self.min_performance = clamped;
+        }
+        warnings
+    }
+}
+
+/// Energy of a boid whose species has an `EnergyConfig`, added and removed as the config changes
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Energy {
+    pub value: f32,
+    // Velocity before the steering rules ran this tick
+    start_velocity: Vec3,
+}
+
+impl Energy {
+    pub fn new(velocity: Vec3) -> Self {
+        Self { value: 1.0, start_velocity: velocity }
+    }
+
+    /// Multiplier of max speed and rule strengths at the current energy
+    pub fn performance(&self, config: &EnergyConfig) -> f32 {
+        config.min_performance + (1.0 - config.min_performance) * self.value
+    }
+}
+
+pub struct EnergyPlugin;
+
+impl Plugin for EnergyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, track_energy.in_set(InSimulationSchedule::Perception))
+            // before captures move any prey, which would count as steering
+            .add_systems(FixedUpdate, apply_fatigue
+                .after(avoid_obstacles)
+                .before(capture_prey)
+                .in_set(InSimulationSchedule::EntityUpdates))
+            // in the same tick as the captures, since events are cleared once a frame rather than once a tick
+            .add_systems(FixedUpdate, feed_predators
+                .after(count_captures)
+                .in_set(InSimulationSchedule::EntityUpdates));
+    }
+}
+
+// Gives energy to boids of species with an energy model, takes it from the others, and records
+// the velocity the rules start from
+fn track_energy(
+    mut commands: Commands,
+    configs: Res<SpeciesConfigs>,
+    mut query: Query<(Entity, &Velocity, &Boid, Option<&mut Energy>)>,
+) {
+    for (entity, velocity, boid, energy) in query.iter_mut() {
+        match (&configs.get(&boid.species).energy, energy) {
+            (Some(_), Some(mut energy)) => energy.start_velocity = velocity.value,
+            (Some(_), None) => {
+                commands.entity(entity).insert(Energy::new(velocity.value));
+            }
+            (None, Some(_)) => {
+                commands.entity(entity).remove::<Energy>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+// Scales down the steering of tired boids, then drains or restores their energy
+fn apply_fatigue(
+    configs: Res<SpeciesConfigs>,
+    time: Res<Time>,
+    mut query: Query<(&mut Velocity, &mut Energy, &Boid)>,
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+    query.par_iter_mut().for_each(|(mut velocity, mut energy, boid)| {
+        let config = configs.get(&boid.species);
+        let Some(energy_config) = &config.energy else {
+            return;
+        };
+        let performance = energy.performance(energy_config);
+        // every rule adds to the velocity, so scaling the change this tick scales all their strengths
+        let steering = (velocity.value - energy.start_velocity) * performance;
+        let max_speed = config.max_speed * performance;
+        velocity.value = bound_vector(energy.start_velocity + steering, config.min_speed.min(max_speed), max_speed);
+
+        let max_speed = config.max_speed.max(f32::EPSILON);
+        let speed = velocity.value.length() / max_speed;
+        let acceleration = (velocity.value - energy.start_velocity).length() / delta / max_speed;
+        let rate = energy_config.rest_recovery * (1.0 - speed).max(0.0)
+            - energy_config.speed_cost * speed * speed
+            - energy_config.acceleration_cost * acceleration;
+        energy.value = (energy.value + rate * delta).clamp(0.0, 1.0);
+    });
+}
+
+fn feed_predators(
+    configs: Res<SpeciesConfigs>,
+    mut captured_events: EventReader<PreyCaptured>,
+    mut predators: Query<(&mut Energy, &Boid)>,
+) {
+    for event in captured_events.read() {
+        if let Ok((mut energy, boid)) = predators.get_mut(event.predator) {
+            if let Some(energy_config) = &configs.get(&boid.species).energy {
+                energy.value = (energy.value + energy_config.feeding_gain).min(1.0);
+            }
+        }
+    }
+}
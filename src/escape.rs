@@ -0,0 +1,143 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boid_config::SpeciesConfigs,
+    boid_map::BoidMap,
+    flock::{bound_vector, predator_prey_rules, Boid, Predator},
+    moveable::{Dimensions, Velocity},
+    obstacle::avoid_obstacles,
+    simulation_schedule::InSimulationSchedule,
+};
+
+// Panic below this level is dropped, so that it does not spread forever at ever smaller levels
+const MIN_PANIC: f32 = 0.05;
+
+/// How panicked prey move relative to the predator they are escaping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EscapePattern {
+    // Steer directly away from the predator
+    #[default]
+    Flee,
+    // Dart away from the predator at full speed, so the school bursts outwards from it
+    FlashExpansion,
+    // Split to either side of the predator's path and swim back past it, rejoining behind it
+    Fountain,
+    // Keep a clear space around the predator, circling it at half the detection range
+    Herd,
+}
+
+/// How alarmed a prey is, and by which predator. Panic comes from seeing a predator or from
+/// panicked neighbours, and fades over time
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Panic {
+    // 1 when a predator is in sight, lower when only heard about from neighbours
+    pub level: f32,
+    // Last known position and velocity of the predator
+    pub threat: Vec3,
+    pub threat_velocity: Vec3,
+}
+
+impl Panic {
+    /// Direction a prey at `position` escapes in, before scaling by the panic level
+    pub fn escape_direction(&self, pattern: EscapePattern, position: Vec3, detection_range: f32) -> Vec3 {
+        let offset = position - self.threat;
+        let away = offset.normalize_or_zero();
+        let heading = self.threat_velocity.normalize_or_zero();
+        match pattern {
+            EscapePattern::Flee | EscapePattern::FlashExpansion => away,
+            EscapePattern::Fountain => {
+                // prey straight ahead of the predator pick a side instead of swimming in front of it
+                let side = offset.reject_from_normalized(heading).try_normalize().unwrap_or_else(|| heading.any_orthonormal_vector());
+                if offset.dot(heading) > 0.0 {
+                    side - heading * 0.5
+                } else {
+                    // once past the predator, swim back in behind it
+                    -heading - side * 0.5
+                }
+            }
+            EscapePattern::Herd => {
+                // circle around the predator towards its tail, moving out when too close and in when too far
+                let around = (-heading).reject_from_normalized(away).normalize_or_zero();
+                let keep = detection_range * 0.5;
+                let radial = ((keep - offset.length()) / keep.max(f32::EPSILON)).clamp(-1.0, 1.0);
+                around + away * radial
+            }
+        }
+    }
+}
+
+pub struct EscapePlugin;
+
+impl Plugin for EscapePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (update_panic, escape_predators)
+            .chain()
+            .after(predator_prey_rules)
+            .before(avoid_obstacles)
+            .in_set(InSimulationSchedule::EntityUpdates));
+    }
+}
+
+// Prey seeing a predator panic fully, the rest pick up a fraction of the panic of their most
+// panicked neighbour on the previous tick, so alarm spreads through the school one neighbour per tick
+pub fn update_panic(
+    mut prey: Query<(Entity, &Transform, &mut Panic, &Boid), Without<Predator>>,
+    configs: Res<SpeciesConfigs>,
+    map: Res<BoidMap>,
+    time: Res<Time>,
+    mut previous: Local<HashMap<Entity, Panic>>,
+) {
+    previous.clear();
+    previous.extend(prey.iter().filter(|(.., panic, _)| panic.level > 0.0).map(|(entity, _, panic, _)| (entity, *panic)));
+
+    prey.par_iter_mut().for_each(|(entity, transform, mut panic, boid)| {
+        let config = configs.get(&boid.species);
+        let position = transform.translation;
+        let seen = map.within(position, config.detection_range)
+            .filter(|entry| entry.predator)
+            .min_by(|a, b| a.position.distance_squared(position).total_cmp(&b.position.distance_squared(position)));
+        if let Some(predator) = seen {
+            *panic = Panic { level: 1.0, threat: predator.position, threat_velocity: predator.velocity };
+            return;
+        }
+
+        panic.level = (panic.level - config.panic_decay * time.delta_seconds()).max(0.0);
+        if config.panic_transfer > 0.0 && !previous.is_empty() {
+            let heard = map.within(position, config.alignment_range)
+                .filter(|entry| !entry.predator && entry.entity != entity)
+                .filter_map(|entry| previous.get(&entry.entity))
+                .max_by(|a, b| a.level.total_cmp(&b.level));
+            if let Some(heard) = heard {
+                let level = heard.level * config.panic_transfer;
+                if level > panic.level {
+                    *panic = Panic { level, ..*heard };
+                }
+            }
+        }
+        if panic.level < MIN_PANIC {
+            panic.level = 0.0;
+        }
+    });
+}
+
+pub fn escape_predators(
+    mut prey: Query<(&Transform, &mut Velocity, &Panic, &Boid)>,
+    configs: Res<SpeciesConfigs>,
+    dimensions: Res<Dimensions>,
+    time: Res<Time>,
+) {
+    prey.par_iter_mut().for_each(|(transform, mut velocity, panic, boid)| {
+        if panic.level <= 0.0 {
+            return;
+        }
+        let config = configs.get(&boid.species);
+        let direction = dimensions.constrain(panic.escape_direction(config.escape_pattern, transform.translation, config.detection_range)).normalize_or_zero();
+        velocity.value = match config.escape_pattern {
+            // an immediate change of course rather than a steering force
+            EscapePattern::FlashExpansion => velocity.value.lerp(direction * config.max_speed, panic.level),
+            _ => velocity.value + direction * config.predator_avoidance_strength * panic.level * time.delta_seconds(),
+        };
+        velocity.value = bound_vector(velocity.value, config.min_speed, config.max_speed);
+    });
+}
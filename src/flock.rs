@@ -1,52 +1,8 @@
-use std::{cmp::max, sync::{Arc, Mutex}};
+use std::collections::BTreeMap;
 
-use bevy::{prelude::*, utils::HashMap};
-use bevy_mod_picking::prelude::*;
+use bevy::prelude::*;
 
-use crate::{asset_loader::SimAssets, moveable::{MoveableObjectBundle, Velocity}, selected::SelectedEvent, simulation_schedule::InSimulationSchedule};
-
-const NUM_BOIDS: usize = 1000;
-const THREE_D: bool = true;
-
-#[derive(Resource, Debug)]
-pub struct BoidConfig {
-    pub min_speed: f32,
-    pub max_speed: f32,
-    
-    // View angle in radians
-    pub view_angle: f32,
-
-    pub separation_strength: f32,
-    pub separation_range: f32,
-    pub alignment_strength: f32,
-    pub alignment_range: f32,
-    pub cohesion_strength: f32,
-    pub cohesion_range: f32,
-
-    pub flock_centre_strength: f32,
-
-    pub predator_strength: f32,
-    pub predator_avoidance_strength: f32,
-}
-
-impl Default for BoidConfig {
-    fn default() -> Self {
-        Self {
-            min_speed: 10.0,
-            max_speed: 30.0,
-            view_angle: f32::to_radians(120.0),
-            separation_strength: 5.0,
-            separation_range: 50.0,
-            alignment_strength: 5.0,
-            alignment_range: 75.0,
-            cohesion_strength: 5.0,
-            cohesion_range: 100.0,
-            flock_centre_strength: 2.0,
-            predator_strength: 30.0,
-            predator_avoidance_strength: 5.0,
-        }
-    }
-}
+use crate::{boid_map::{BoidEntry, BoidMap}, boid_config::{ConfigWarnings, SpeciesConfigs, MAX_TOPOLOGICAL_NEIGHBOURS}, escape::Panic, flock_centre::{update_flock_centres, update_flock_statistics, FlockStatistics}, moveable::{Dimensions, MoveableObjectBundle, Velocity}, hunting::{hunting_target, pack_target, HuntingStrategy, Pack, Wander}, predation::KillCount, predator::Territory, rng::SimRng, simulation_schedule::{InSimulationSchedule, TransformInterpolation}, spawn_distribution::{random_direction, InitialState, SpawnDistribution}, species::{Role, SpeciesDefinition, SpeciesManifest}};
 
 #[derive(Component, Debug)]
 pub struct Flock {
@@ -56,211 +12,195 @@ pub struct Flock {
 
 #[derive(Component)]
 pub struct Boid {
-    pub model: String,
+    pub species: String,
 }
  #[derive(Component)]
  pub struct Predator;
 
-#[derive(Resource)]
-pub struct BoidMap {
-    pub map: HashMap<(isize, isize, isize), Vec<Entity>>,
-    pub resolution: usize,
-}
-
-impl Default for BoidMap {
-    fn default() -> Self {
-        Self {
-            map: HashMap::new(),
-            resolution: 0,
-        }
-    }
-}
-
-impl BoidMap {
-    pub fn reset(&mut self) {
-        self.map.clear();
-    }
-
-    pub fn add_boid(&mut self, boid: Entity, position: Vec3) {
-        let (x, y, z) = self.vec3_to_grid(position);
-        let boids = self.map.entry((x, y, z)).or_insert_with(Vec::new);
-        boids.push(boid);
-    }
-
-    pub fn vec3_to_grid(&self, position: Vec3) -> (isize, isize, isize) {
-        // floor the position based on the resolution
-        let conversion = |x: f32| -> isize {
-            if x < 0.0 {
-                // subtract 1 to make sure the conversion is always towards negative
-                (x as isize / self.resolution as isize) - 1
-            } else {
-                x as isize / self.resolution as isize
-            }
-        };
-        (
-            conversion(position.x),
-            conversion(position.y),
-            conversion(position.z),
-        )
-    }
+/// Position of a boid in the order boids were spawned, counting from 0 over the whole run
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SpawnOrder(pub u64);
 
-    pub fn get_possible_neighbours(&self, position: Vec3) -> Vec<Entity> {
-        let (x, y, z) = self.vec3_to_grid(position);
-        let mut boids = Vec::new();
-        // Iterate over the 3x3x3 grid around the boid to collect all possible neighbours
-        for i in -1..=1 {
-            for j in -1..=1 {
-                for k in -1..=1 {
-                    boids.extend(
-                        self.map.get(&(x + i, y + j, z + k)).unwrap_or(&Vec::new())
-                    );
-                }
-            }
-        }
-        boids
-    }
-}
+// Next `SpawnOrder` to hand out
+#[derive(Resource, Default)]
+struct SpawnCounter(u64);
 
 pub struct FlockPlugin;
 
 impl Plugin for FlockPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, spawn_flock)
-            .init_resource::<BoidConfig>()
+            .init_resource::<SpeciesConfigs>()
             .init_resource::<BoidMap>()
-            .add_systems(Update, (
+            .init_resource::<ConfigWarnings>()
+            .init_resource::<FlockStatistics>()
+            .init_resource::<SpawnCounter>()
+            .add_systems(Startup, validate_boid_config.before(spawn_flock))
+            .add_systems(FixedUpdate, (
+                validate_boid_config,
                 update_boid_map, // spatial partitioning runs first
-                (apply_boids_rules, apply_flock_centre, predator_prey_rules),
+                update_flock_statistics,
+                update_flock_centres,
+            ).chain().in_set(InSimulationSchedule::Perception))
+            .add_systems(FixedUpdate, (
+                // rules run in a fixed order so that ticks are reproducible
+                apply_boids_rules,
+                apply_flock_centre,
+                predator_prey_rules,
             ).chain().in_set(InSimulationSchedule::EntityUpdates));
-            
     }
-    
 }
 
 fn spawn_flock(
     mut commands: Commands,
-    assets: Res<SimAssets>,
-    config: Res<BoidConfig>,
+    manifest: Res<SpeciesManifest>,
+    configs: Res<SpeciesConfigs>,
+    dimensions: Res<Dimensions>,
+    distribution: Res<SpawnDistribution>,
+    mut rng: ResMut<SimRng>,
 ) {
-    //space boids out depending on the number of boids
-    let spatial_separation = 100.0 * (NUM_BOIDS as f32).sqrt();
-    for _ in 0..NUM_BOIDS {
-        let transform = Transform::from_xyz(
-            rand::random::<f32>() * spatial_separation - spatial_separation / 2.0,
-            0.0,
-            rand::random::<f32>() * spatial_separation - spatial_separation / 2.0,
-        );
-        commands.spawn((
-            MoveableObjectBundle {
-                velocity: Velocity::new(Vec3::new(
-                    rand::random::<f32>(),
-                    if THREE_D {
-                        rand::random::<f32>()
-                    } else {
-                        0.0
-                    },
-                    rand::random::<f32>(),
-                ) * config.min_speed),
-                model: SceneBundle {
-                    scene: assets.models.get("Fish").expect("Model 'Fish' shoulds exist").clone(),
-                    transform,
-                    ..default()
-                },
-            },
-            Flock {
-                identity: 0,
-                centre: Vec3::ZERO,
-            },
-            Boid {
-                model: "Fish".to_string(),
-            },
-            PickableBundle::default(),
-            // Creates an event when the entity is clicked
-            On::<Pointer<Click>>::send_event::<SelectedEvent>(),
-        ));
+    spawn_manifest(&mut commands, &manifest, &configs, *dimensions, &distribution, &mut rng);
+}
+
+/// Spawns every species in the manifest, placed by the given distribution
+pub fn spawn_manifest(commands: &mut Commands, manifest: &SpeciesManifest, configs: &SpeciesConfigs, dimensions: Dimensions, distribution: &SpawnDistribution, rng: &mut SimRng) {
+    if let SpawnDistribution::File { path } = distribution {
+        match InitialState::load(path) {
+            Ok(state) => {
+                spawn_initial_state(commands, manifest, &state, dimensions);
+                return;
+            }
+            Err(e) => warn!("Failed to load initial state '{}', spawning uniformly instead: {}", path.display(), e),
+        }
+    }
+    let total = manifest.total_count();
+    for (identity, species) in manifest.species.iter().enumerate() {
+        for _ in 0..species.count {
+            let (position, velocity) = distribution.sample(rng, total, configs.get(&species.name).min_speed, dimensions);
+            spawn_boid(commands, species, identity, Transform::from_translation(position), velocity);
+        }
     }
+}
 
-    for _ in 0..5 {
-        let transform = Transform::from_xyz(
-            rand::random::<f32>() * spatial_separation - spatial_separation / 2.0,
-            0.0,
-            rand::random::<f32>() * spatial_separation - spatial_separation / 2.0,
-        );
-        commands.spawn((
-            MoveableObjectBundle {
-                velocity: Velocity::new(Vec3::new(
-                    rand::random::<f32>(),
-                    if THREE_D {
-                        rand::random::<f32>()
-                    } else {
-                        0.0
-                    },
-                    rand::random::<f32>(),
-                ) * config.min_speed),
-                model: SceneBundle {
-                    scene: assets.models.get("Shark").expect("Model 'Shark' should exist").clone(),
-                    transform,
-                    ..default()
-                },
-            },
-            Flock {
-                identity: 1,
-                centre: Vec3::ZERO,
-            },
-            Boid {
-                model: "Shark".to_string(),
-            },
-            Predator,
-            PickableBundle::default(),
-        )); 
+fn spawn_initial_state(commands: &mut Commands, manifest: &SpeciesManifest, state: &InitialState, dimensions: Dimensions) {
+    for boid in &state.boids {
+        let Some(identity) = manifest.species.iter().position(|species| species.name == boid.species) else {
+            warn!("Skipping boid of unknown species '{}' in initial state", boid.species);
+            continue;
+        };
+        let transform = Transform::from_translation(dimensions.constrain(boid.position));
+        spawn_boid(commands, &manifest.species[identity], identity, transform, dimensions.constrain(boid.velocity));
     }
 }
 
-fn update_boid_map(
+/// Velocity of the given speed in a uniformly random direction
+pub fn random_velocity(rng: &mut SimRng, speed: f32, dimensions: Dimensions) -> Vec3 {
+    random_direction(rng, dimensions) * speed
+}
+
+/// Spawns a boid of the given species, `identity` being the index of the species in the manifest
+pub fn spawn_boid(commands: &mut Commands, species: &SpeciesDefinition, identity: usize, transform: Transform, velocity: Vec3) -> Entity {
+    let transform = transform.with_scale(Vec3::splat(species.scale));
+    let mut boid = commands.spawn((
+        MoveableObjectBundle {
+            velocity: Velocity::new(velocity),
+            interpolation: TransformInterpolation::new(transform),
+            spatial: SpatialBundle::from_transform(transform),
+        },
+        Flock {
+            identity,
+            centre: Vec3::ZERO,
+        },
+        Boid {
+            species: species.name.clone(),
+        },
+    ));
+    if species.role == Role::Predator {
+        boid.insert((Predator, KillCount::default(), Wander::new(velocity), Territory { centre: transform.translation }));
+    } else {
+        boid.insert(Panic::default());
+    }
+    let entity = boid.id();
+    // numbered as the commands are applied, which is the order the boids are spawned in
+    commands.add(move |world: &mut World| {
+        let mut counter = world.resource_mut::<SpawnCounter>();
+        let order = SpawnOrder(counter.0);
+        counter.0 += 1;
+        world.entity_mut(entity).insert(order);
+    });
+    entity
+}
+
+fn validate_boid_config(mut config: ResMut<SpeciesConfigs>, mut warnings: ResMut<ConfigWarnings>) {
+    if !config.is_changed() {
+        return;
+    }
+    // avoid flagging the config as changed again when nothing was wrong
+    let found = config.bypass_change_detection().validate();
+    if !found.is_empty() {
+        for warning in &found {
+            warn!("Invalid boid config: {}", warning);
+        }
+        config.set_changed();
+        // other validators report to the same list, so add to it rather than replacing it
+        warnings.0.extend(found);
+    }
+}
+
+pub fn update_boid_map(
     mut flocks: ResMut<BoidMap>,
-    config: Res<BoidConfig>,
-    query: Query<(Entity, &Transform), With<Boid>>,
+    configs: Res<SpeciesConfigs>,
+    query: Query<(Entity, &Transform, &Velocity, &Flock, Has<Predator>)>,
 ) {
-    flocks.reset();
     //update the resolution of the map, which may have changed due to user input
-    flocks.resolution = max(max(
-        config.separation_range as usize,
-        config.alignment_range as usize,
-    ), config.cohesion_range as usize);
-    for (e, t) in query.iter() {
-        flocks.add_boid( e, t.translation);
+    flocks.reset(configs.cell_size());
+    for (e, t, v, flock, predator) in query.iter() {
+        flocks.add_boid(e, t.translation, v.value, flock.identity, predator);
     }
+    flocks.rebuild();
 }
 
+// Flocking of prey. Predators steer by their own rules in `predator::predator_steering`
 fn apply_boids_rules(
-    mut query: Query<(Entity, &Transform, &mut Velocity, &Flock), With<Boid>>,
-    config: Res<BoidConfig>,
+    mut query: Query<(Entity, &Transform, &mut Velocity, &Flock, &Boid), Without<Predator>>,
+    configs: Res<SpeciesConfigs>,
+    dimensions: Res<Dimensions>,
     time: Res<Time>,
     flocks: Res<BoidMap>,
 ) {
-    let forces: Arc<Mutex<HashMap<Entity, Vec3>>> = Arc::new(Mutex::new(HashMap::new()));
-
-    query.par_iter().for_each( |(entity1, transform1, velocity1, flock1)| {
+    // neighbours are read from the map, which holds the velocities from the start of the tick,
+    // so each boid can update its own velocity without waiting for the others
+    query.par_iter_mut().for_each( |(entity1, transform1, mut velocity1, flock1, boid1)| {
+        let config = configs.get(&boid1.species);
         let mut total_separation = Vec3::ZERO;
         let mut total_alignment = Vec3::ZERO;
         let mut total_cohesion = Vec3::ZERO;
         let mut closest_distance = f32::MAX;
         let mut closest_force = Vec3::ZERO;
-        for entity2 in flocks.get_possible_neighbours(transform1.translation) {
-            // ignore self
-            if entity1 == entity2 {continue};
-            
-            // retrieve the components of the other boid
-            let (_, transform2, velocity2, flock2) = query.get(entity2).unwrap();
+        let counts = config.neighbourhood.counts();
+        // nearest boids of any flock for separation, and of the same flock for alignment and cohesion
+        let mut nearest = NearestNeighbours::new(counts.map_or(0, |(separation, _, _)| separation));
+        let mut nearest_in_flock = NearestNeighbours::new(counts.map_or(0, |(_, alignment, cohesion)| alignment.max(cohesion)));
+        let range = config.separation_range.max(config.alignment_range).max(config.cohesion_range);
+        for other in flocks.within(transform1.translation, range) {
+            // ignore self, and predators, which prey escape through their panic rather than separation
+            if entity1 == other.entity || other.predator {continue};
 
             // check if the other boid is within the view angle
-            let angle = velocity1.value.angle_between(transform2.translation - transform1.translation);
+            let angle = velocity1.value.angle_between(other.position - transform1.translation);
             if angle > config.view_angle {continue};
 
-            let distance = transform1.translation.distance(transform2.translation);
+            let distance = transform1.translation.distance(other.position);
+            if counts.is_some() {
+                nearest.insert(distance, other);
+                if flock1.identity == other.flock {
+                    nearest_in_flock.insert(distance, other);
+                }
+                continue;
+            }
             if distance < config.separation_range {
                 // values are normalised so that all boids have the same influence
-                let separation = (transform1.translation - transform2.translation).normalize_or_zero();
+                let separation = (transform1.translation - other.position).normalize_or_zero();
                 total_separation += separation;
                 if distance < closest_distance{
                     closest_distance = distance;
@@ -269,67 +209,137 @@ fn apply_boids_rules(
             }
 
             // If not in the same flock, ignore alignment and cohesion
-            if flock1.identity != flock2.identity {continue};
+            if flock1.identity != other.flock {continue};
 
             if distance < config.alignment_range {
-                let alignment = velocity2.value.normalize_or_zero();
+                let alignment = other.velocity.normalize_or_zero();
                 total_alignment += alignment;
             }
             if distance < config.cohesion_range {
-                let cohesion = (transform2.translation - transform1.translation).normalize_or_zero();
+                let cohesion = (other.position - transform1.translation).normalize_or_zero();
                 total_cohesion += cohesion;
             }
         }
+        if let Some((_, alignment_count, cohesion_count)) = counts {
+            // the nearest neighbours can be far off in a sparse flock, only those within range are too close
+            let too_close = || nearest.iter().filter(|other| transform1.translation.distance(other.position) < config.separation_range);
+            for other in too_close() {
+                total_separation += (transform1.translation - other.position).normalize_or_zero();
+            }
+            if let Some(closest) = too_close().next() {
+                closest_force = (transform1.translation - closest.position).normalize_or_zero();
+            }
+            for other in nearest_in_flock.iter().take(alignment_count) {
+                total_alignment += other.velocity.normalize_or_zero();
+            }
+            for other in nearest_in_flock.iter().take(cohesion_count) {
+                total_cohesion += (other.position - transform1.translation).normalize_or_zero();
+            }
+        }
         // values are nomalised so that all forces have the same base influence, regardless of amount of boids in each forces range
         let force = total_separation.normalize_or_zero() * config.separation_strength
             + total_alignment.normalize_or_zero() * config.alignment_strength
             + total_cohesion.normalize_or_zero() * config.cohesion_strength
             + closest_force * config.separation_strength;
-        if let Ok(mut forces) = forces.lock() {
-            forces.insert(entity1, force);
-        }
-        // forces.insert(entity1, force);
+        let force = dimensions.constrain(force);
+        velocity1.value = bound_vector(velocity1.value + force * time.delta_seconds(), config.min_speed, config.max_speed);
     });
+}
 
-    let forces = forces.lock().unwrap();
+// The closest boids seen so far, nearest first, kept on the stack so the rules do not allocate
+struct NearestNeighbours<'a> {
+    capacity: usize,
+    len: usize,
+    neighbours: [(f32, Option<&'a BoidEntry>); MAX_TOPOLOGICAL_NEIGHBOURS],
+}
 
-    query.par_iter_mut().for_each( |(e, _, mut v, _)| {
-        let force = *forces.get(&e).unwrap_or(&Vec3::ZERO);
-        v.value = bound_vector(v.value + force * time.delta_seconds(), config.min_speed, config.max_speed);
-    });
+impl<'a> NearestNeighbours<'a> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.min(MAX_TOPOLOGICAL_NEIGHBOURS),
+            len: 0,
+            neighbours: [(f32::MAX, None); MAX_TOPOLOGICAL_NEIGHBOURS],
+        }
+    }
+
+    fn insert(&mut self, distance: f32, entry: &'a BoidEntry) {
+        if self.capacity == 0 || (self.len == self.capacity && distance >= self.neighbours[self.len - 1].0) {
+            return;
+        }
+        // the furthest neighbour drops off the end when full
+        let mut i = self.len.min(self.capacity - 1);
+        while i > 0 && self.neighbours[i - 1].0 > distance {
+            self.neighbours[i] = self.neighbours[i - 1];
+            i -= 1;
+        }
+        self.neighbours[i] = (distance, Some(entry));
+        self.len = (self.len + 1).min(self.capacity);
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &'a BoidEntry> + '_ {
+        self.neighbours[..self.len].iter().filter_map(|(_, entry)| *entry)
+    }
 }
 
-fn predator_prey_rules(
-    mut predators: Query<(&Transform, &mut Velocity), With<Predator>>,
-    mut prey: Query<(&Transform, &mut Velocity), Without<Predator>>,
-    config: Res<BoidConfig>,
+pub fn predator_prey_rules(
+    mut predators: Query<(&Transform, &mut Velocity, &mut Wander, &Boid, &Flock), With<Predator>>,
+    configs: Res<SpeciesConfigs>,
+    dimensions: Res<Dimensions>,
     time: Res<Time>,
     flocks: Res<BoidMap>,
+    mut rng: ResMut<SimRng>,
+    mut packs: Local<BTreeMap<usize, Pack>>,
 ) {
-    predators.iter_mut().for_each(|(predator_transform, mut predator_velocity)| {
-       let mut closest = Vec3::MAX;
-       for prey_entity in flocks.get_possible_neighbours(predator_transform.translation) {
-          if let Ok((prey_transform, mut prey_velocity)) = prey.get_mut(prey_entity) {
-                let distance = predator_transform.translation.distance(prey_transform.translation);
-                if distance < predator_transform.translation.distance(closest) {
-                    closest = prey_transform.translation;
-                }
-                // Prey avoids predator
-                prey_velocity.value = bound_vector(prey_velocity.value + (prey_transform.translation - predator_transform.translation).normalize_or_zero() * config.predator_avoidance_strength * time.delta_seconds(), config.min_speed, config.max_speed);
-          }
-       }
-       // Predator chases the closest prey
-       predator_velocity.value = bound_vector(predator_velocity.value + (closest - predator_transform.translation).normalize_or_zero() * config.predator_strength * time.delta_seconds(), config.min_speed, config.max_speed);
+    packs.clear();
+    for (transform, _, _, predator, flock) in predators.iter() {
+        if configs.get(&predator.species).hunting_strategy == HuntingStrategy::Pack {
+            let pack = packs.entry(flock.identity).or_insert_with(|| Pack {
+                species: predator.species.clone(),
+                centroid: Vec3::ZERO,
+                members: 0,
+                target: None,
+            });
+            pack.centroid += transform.translation;
+            pack.members += 1;
+        }
+    }
+    for pack in packs.values_mut() {
+        pack.centroid /= pack.members as f32;
+    }
+    for pack in packs.values_mut() {
+        // a pack of one hunts alone, as do the members of a pack too scattered to find prey near its middle
+        if pack.members > 1 {
+            pack.target = pack_target(&flocks, configs.get(&pack.species), pack.centroid).copied();
+        }
+    }
+
+    predators.iter_mut().for_each(|(predator_transform, mut predator_velocity, mut wander, predator, predator_flock)| {
+       let predator_config = configs.get(&predator.species);
+       let pack = packs.get(&predator_flock.identity)
+           .and_then(|pack| pack.target.as_ref().map(|target| (pack.centroid, target)));
+       let target = hunting_target(&flocks, predator_config, predator_transform.translation, predator_velocity.value.length(), pack);
+       let force = match target {
+           // Predator chases its prey
+           Some(target) => (target - predator_transform.translation).normalize_or_zero() * predator_config.predator_strength,
+           // With no prey in sight, drift along a slowly turning heading
+           None => {
+               let jitter = random_direction(&mut rng, *dimensions) * predator_config.wander_jitter * time.delta_seconds();
+               wander.heading = dimensions.constrain(wander.heading + jitter).try_normalize().unwrap_or(wander.heading);
+               wander.heading * predator_config.wander_strength
+           }
+       };
+       predator_velocity.value = bound_vector(predator_velocity.value + dimensions.constrain(force) * time.delta_seconds(), predator_config.min_speed, predator_config.max_speed);
     });
 }
 
 
-fn apply_flock_centre(
-    mut query: Query<(&Flock, &Transform, &mut Velocity), With<Boid>>,
-    config: Res<BoidConfig>,
+pub fn apply_flock_centre(
+    mut query: Query<(&Flock, &Transform, &mut Velocity, &Boid)>,
+    configs: Res<SpeciesConfigs>,
     time: Res<Time>,
 ) {
-    for (flock, transform, mut velocity) in query.iter_mut() {
+    for (flock, transform, mut velocity, boid) in query.iter_mut() {
+        let config = configs.get(&boid.species);
         let force = (flock.centre - transform.translation).normalize_or_zero() * config.flock_centre_strength;
         // Boids move to the flock centre
         // mainly used to maintain the flock in a certain area
@@ -337,7 +347,7 @@ fn apply_flock_centre(
     }
 }
 
-fn bound_vector(mut vector: Vec3, min: f32, max: f32) -> Vec3 {
+pub fn bound_vector(mut vector: Vec3, min: f32, max: f32) -> Vec3 {
     if vector.length() > max {
         vector = vector.normalize_or_zero() * max;
     } else if vector.length() < min {
@@ -346,3 +356,76 @@ fn bound_vector(mut vector: Vec3, min: f32, max: f32) -> Vec3 {
     vector
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(count: u32) -> BoidMap {
+        let mut map = BoidMap::default();
+        map.reset(1.0);
+        for i in 0..count {
+            map.add_boid(Entity::from_raw(i), Vec3::ZERO, Vec3::ZERO, 0, false);
+        }
+        map.rebuild();
+        map
+    }
+
+    fn kept(nearest: &NearestNeighbours) -> Vec<u32> {
+        nearest.iter().map(|entry| entry.entity.index()).collect()
+    }
+
+    #[test]
+    fn keeps_the_nearest_in_order() {
+        let map = entries(6);
+        let mut nearest = NearestNeighbours::new(3);
+        // entity i is at distance DISTANCES[i]
+        const DISTANCES: [f32; 6] = [5.0, 1.0, 4.0, 3.0, 0.5, 2.0];
+        for entry in map.entries() {
+            nearest.insert(DISTANCES[entry.entity.index() as usize], entry);
+        }
+        assert_eq!(kept(&nearest), vec![4, 1, 5]);
+    }
+
+    #[test]
+    fn fewer_than_capacity() {
+        let map = entries(2);
+        let mut nearest = NearestNeighbours::new(4);
+        for entry in map.entries() {
+            nearest.insert(10.0 - entry.entity.index() as f32, entry);
+        }
+        assert_eq!(kept(&nearest), vec![1, 0]);
+    }
+
+    #[test]
+    fn ties_with_the_furthest_are_dropped_when_full() {
+        let map = entries(3);
+        let mut nearest = NearestNeighbours::new(2);
+        for entry in map.entries() {
+            nearest.insert(1.0, entry);
+        }
+        // the first two inserted stay
+        let first: Vec<u32> = map.entries()[..2].iter().map(|entry| entry.entity.index()).collect();
+        assert_eq!(kept(&nearest), first);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let map = entries(2);
+        let mut nearest = NearestNeighbours::new(0);
+        for entry in map.entries() {
+            nearest.insert(1.0, entry);
+        }
+        assert_eq!(nearest.iter().count(), 0);
+    }
+
+    #[test]
+    fn capacity_is_capped() {
+        let map = entries(MAX_TOPOLOGICAL_NEIGHBOURS as u32 + 5);
+        let mut nearest = NearestNeighbours::new(MAX_TOPOLOGICAL_NEIGHBOURS + 10);
+        for entry in map.entries() {
+            nearest.insert(entry.entity.index() as f32, entry);
+        }
+        assert_eq!(kept(&nearest), (0..MAX_TOPOLOGICAL_NEIGHBOURS as u32).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boid_config::SpeciesConfigs,
+    flock::{Boid, Flock},
+    moveable::Velocity,
+};
+
+/// What a flock is pulled towards by the flock centre rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CentreMode {
+    // The current centroid of the flock
+    OwnCentroid,
+    // A fixed point in the world
+    HomeAnchor { position: Vec3 },
+    // A point travelling around a horizontal circle, taking `period` seconds per lap
+    Waypoint { centre: Vec3, radius: f32, period: f32 },
+}
+
+impl Default for CentreMode {
+    fn default() -> Self {
+        CentreMode::HomeAnchor { position: Vec3::ZERO }
+    }
+}
+
+impl CentreMode {
+    pub fn target(&self, stats: Option<&FlockStats>, elapsed_seconds: f32) -> Vec3 {
+        match *self {
+            CentreMode::OwnCentroid => stats.map(|stats| stats.centroid).unwrap_or(Vec3::ZERO),
+            CentreMode::HomeAnchor { position } => position,
+            CentreMode::Waypoint { centre, radius, period } => {
+                let angle = if period > 0.0 { elapsed_seconds / period * std::f32::consts::TAU } else { 0.0 };
+                centre + Vec3::new(angle.cos(), 0.0, angle.sin()) * radius
+            }
+        }
+    }
+}
+
+/// Summary of a flock's members, recomputed every tick
+#[derive(Debug, Clone, Default)]
+pub struct FlockStats {
+    pub count: usize,
+    pub centroid: Vec3,
+    // Mean velocity
+    pub velocity: Vec3,
+    // Root mean square distance of members from the centroid
+    pub spread: f32,
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+/// [`FlockStats`] of every flock, keyed by [`Flock::identity`]
+#[derive(Resource, Debug, Default)]
+pub struct FlockStatistics {
+    pub flocks: BTreeMap<usize, FlockStats>,
+}
+
+pub fn update_flock_statistics(
+    mut statistics: ResMut<FlockStatistics>,
+    boids: Query<(&Flock, &Transform, &Velocity), With<Boid>>,
+) {
+    statistics.flocks.clear();
+    for (flock, transform, velocity) in boids.iter() {
+        let position = transform.translation;
+        let stats = statistics.flocks.entry(flock.identity).or_insert_with(|| FlockStats {
+            min: position,
+            max: position,
+            ..default()
+        });
+        stats.count += 1;
+        stats.centroid += position;
+        stats.velocity += velocity.value;
+        stats.min = stats.min.min(position);
+        stats.max = stats.max.max(position);
+    }
+    for stats in statistics.flocks.values_mut() {
+        stats.centroid /= stats.count as f32;
+        stats.velocity /= stats.count as f32;
+    }
+    // spread needs the centroid, so takes a second pass
+    for (flock, transform, _) in boids.iter() {
+        if let Some(stats) = statistics.flocks.get_mut(&flock.identity) {
+            stats.spread += transform.translation.distance_squared(stats.centroid);
+        }
+    }
+    for stats in statistics.flocks.values_mut() {
+        stats.spread = (stats.spread / stats.count as f32).sqrt();
+    }
+}
+
+pub fn update_flock_centres(
+    statistics: Res<FlockStatistics>,
+    configs: Res<SpeciesConfigs>,
+    time: Res<Time>,
+    mut boids: Query<(&mut Flock, &Boid)>,
+) {
+    for (mut flock, boid) in boids.iter_mut() {
+        let stats = statistics.flocks.get(&flock.identity);
+        flock.centre = configs.get(&boid.species).centre_mode.target(stats, time.elapsed_seconds());
+    }
+}
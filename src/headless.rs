@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use bevy::{log::LogPlugin, prelude::*, time::TimeUpdateStrategy};
+
+use crate::{
+    flock::{Boid, Predator},
+    moveable::Velocity,
+    predation::PredationStats,
+    simulation_schedule::{SimulationSettings, SimulationTick},
+    SimulationPlugin,
+};
+
+/// Builds an app that runs the given [`SimulationPlugin`] on `MinimalPlugins`, with time advancing
+/// by exactly one tick per update so a run does not depend on how fast the machine is
+pub fn headless_app(simulation: SimulationPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(LogPlugin::default())
+        .add_plugins(simulation);
+
+    let tick_rate = app.world.resource::<SimulationSettings>().tick_rate;
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / tick_rate)));
+
+    app.finish();
+    app.cleanup();
+    app
+}
+
+/// Updates the app until the given number of simulation ticks have run in total
+pub fn run_ticks(app: &mut App, ticks: u64) {
+    while app.world.resource::<SimulationTick>().0 < ticks {
+        app.update();
+    }
+}
+
+/// Prints the tick count, the number of prey caught, and the centroid, mean speed and polarisation of the prey
+pub fn print_summary(world: &mut World) {
+    let tick = world.resource::<SimulationTick>().0;
+    let captured: u64 = world.get_resource::<PredationStats>().map_or(0, |stats| stats.losses.values().sum());
+    let mut prey = world.query_filtered::<(&Transform, &Velocity), (With<Boid>, Without<Predator>)>();
+
+    let mut count = 0;
+    let mut centroid = Vec3::ZERO;
+    let mut total_speed = 0.0;
+    let mut total_heading = Vec3::ZERO;
+    for (transform, velocity) in prey.iter(world) {
+        count += 1;
+        centroid += transform.translation;
+        total_speed += velocity.value.length();
+        total_heading += velocity.value.normalize_or_zero();
+    }
+    if count == 0 {
+        println!("tick {}: no prey left", tick);
+        return;
+    }
+    let count_f = count as f32;
+    println!("tick: {}", tick);
+    println!("prey: {}", count);
+    println!("captured: {}", captured);
+    println!("centroid: {:?}", centroid / count_f);
+    println!("mean speed: {:.3}", total_speed / count_f);
+    // 1.0 when every boid heads the same way, close to 0.0 for a disordered flock
+    println!("polarisation: {:.3}", total_heading.length() / count_f);
+}
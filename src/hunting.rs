@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{boid_config::BoidConfig, boid_map::{BoidEntry, BoidMap}};
+
+// Predators do not predict further ahead than this many seconds when intercepting
+const MAX_INTERCEPT_TIME: f32 = 5.0;
+
+/// How a predator picks what to chase among the prey within its `predator_range`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HuntingStrategy {
+    // The closest prey
+    #[default]
+    Nearest,
+    // The centroid of all prey in range, diving into the thick of the flock
+    Centroid,
+    // The prey with the fewest flockmates within the predator's cohesion range, which is
+    // usually on the edge of the flock
+    Isolated,
+    // Where the closest prey will be by the time the predator reaches it
+    Intercept,
+    // Predators of the same species chase the prey closest to the middle of their pack, each
+    // closing in from its own side
+    Pack,
+}
+
+/// Heading a predator drifts along while no prey is in range
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Wander {
+    pub heading: Vec3,
+}
+
+impl Wander {
+    pub fn new(velocity: Vec3) -> Self {
+        Self {
+            heading: velocity.try_normalize().unwrap_or(Vec3::X),
+        }
+    }
+}
+
+/// Predators of the same flock hunting with `HuntingStrategy::Pack`
+#[derive(Debug, Clone)]
+pub struct Pack {
+    pub species: String,
+    pub centroid: Vec3,
+    pub members: usize,
+    // Prey closest to the middle of the pack, which every member chases
+    pub target: Option<BoidEntry>,
+}
+
+/// Point a predator at `position` steers towards, `None` when there is no prey in range.
+/// `pack` is the centroid of the predator's pack and the prey it is chasing, for `HuntingStrategy::Pack`
+pub fn hunting_target(map: &BoidMap, config: &BoidConfig, position: Vec3, speed: f32, pack: Option<(Vec3, &BoidEntry)>) -> Option<Vec3> {
+    let prey = || map.within(position, config.predator_range).filter(|entry| !entry.predator);
+    let nearest = || prey().min_by(|a, b| a.position.distance_squared(position).total_cmp(&b.position.distance_squared(position)));
+    match config.hunting_strategy {
+        HuntingStrategy::Nearest => nearest().map(|entry| entry.position),
+        HuntingStrategy::Centroid => {
+            let (total, count) = prey().fold((Vec3::ZERO, 0), |(total, count), entry| (total + entry.position, count + 1));
+            (count > 0).then(|| total / count as f32)
+        }
+        HuntingStrategy::Isolated => prey()
+            .map(|entry| {
+                let flockmates = map.within(entry.position, config.cohesion_range)
+                    .filter(|other| other.flock == entry.flock && !other.predator)
+                    .count();
+                (flockmates, entry.position.distance_squared(position), entry.position)
+            })
+            // ties go to the closest
+            .min_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map(|(_, _, target)| target),
+        HuntingStrategy::Intercept => nearest().map(|entry| intercept(position, speed, entry)),
+        HuntingStrategy::Pack => match pack {
+            Some((centroid, target)) => {
+                let aim = intercept(position, speed, target);
+                // approach from the side of the pack this predator is on, closing in as it gets near
+                let side = (position - centroid).normalize_or_zero();
+                let distance = position.distance(aim);
+                Some(aim + side * (distance * 0.5).min(config.separation_range))
+            }
+            // on its own, hunt like a lone predator
+            None => nearest().map(|entry| intercept(position, speed, entry)),
+        },
+    }
+}
+
+/// Prey closest to the middle of a pack, within the predator range of its centroid
+pub fn pack_target<'a>(map: &'a BoidMap, config: &BoidConfig, centroid: Vec3) -> Option<&'a BoidEntry> {
+    map.within(centroid, config.predator_range)
+        .filter(|entry| !entry.predator)
+        .min_by(|a, b| a.position.distance_squared(centroid).total_cmp(&b.position.distance_squared(centroid)))
+}
+
+// Where the prey will be when a predator at `position` moving at `speed` reaches it, assuming
+// the prey keeps its velocity
+fn intercept(position: Vec3, speed: f32, prey: &BoidEntry) -> Vec3 {
+    let time = (position.distance(prey.position) / speed.max(f32::EPSILON)).min(MAX_INTERCEPT_TIME);
+    prey.position + prey.velocity * time
+}
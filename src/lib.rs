@@ -0,0 +1,203 @@
+//! Boids-based flocking simulation built on Bevy.
+//!
+//! The simulation is split into plugins that can be used separately:
+//!
+//! - [`SimulationPlugin`] runs the flocking model on a fixed timestep and needs nothing but
+//!   `MinimalPlugins`
+//! - [`RenderingPlugin`] loads the fish models, animations, lighting and camera
+//! - [`GuiPlugin`] adds the egui configuration and population windows, config file hot reloading
+//!   and FPS counter
+//! - [`PickingPlugin`] lets boids be selected by clicking on them
+//!
+//! [`BoidsPlugins`] bundles all four, and is what the viewer binary runs on top of `DefaultPlugins`.
+
+use bevy::{app::PluginGroupBuilder, pbr::wireframe::WireframePlugin, prelude::*};
+use bevy_egui::EguiPlugin;
+use bevy_mod_picking::{picking_core::CorePlugin, DefaultPickingPlugins};
+
+/// Behaviour parameters for each species
+pub mod boid_config;
+/// Spatial grid for neighbour queries
+pub mod boid_map;
+/// Model, animation and scene loading
+pub mod asset_loader;
+/// Edges of the world and what happens to boids reaching them
+pub mod bounds;
+/// Top down camera and its controls
+pub mod camera;
+/// Detecting emergent flocks by clustering boids
+pub mod clustering;
+/// Egui window for tuning the simulation
+pub mod config_gui;
+/// Reloading the config file when it changes on disk
+pub mod config_watcher;
+/// Shapes that follow simulated entities
+pub mod debug;
+/// Fatigue and hunger slowing boids down
+pub mod energy;
+/// Prey panicking and escaping from predators
+pub mod escape;
+/// Boids, flocks and the rules steering them
+pub mod flock;
+/// Flock statistics and what each flock is attracted to
+pub mod flock_centre;
+/// FPS counter overlay
+pub mod fps;
+/// Running the simulation without a window or renderer
+pub mod headless;
+/// How predators choose their prey
+pub mod hunting;
+/// Scene lighting
+pub mod lighting;
+/// Velocity and movement of simulated entities
+pub mod moveable;
+/// Obstacles and the steering rule avoiding them
+pub mod obstacle;
+/// Egui window for placing obstacles
+pub mod obstacle_gui;
+/// Spawning and removing boids while the simulation runs
+pub mod population;
+/// Egui window for spawning and removing boids
+pub mod population_gui;
+/// Predators catching prey
+pub mod predation;
+/// How predators steer relative to each other
+pub mod predator;
+/// Saving and loading config files and presets
+pub mod presets;
+/// Seeded random number generation
+pub mod rng;
+/// Selecting boids with the mouse
+pub mod selected;
+/// Egui window showing the selected boids
+pub mod selected_gui;
+/// Initial positions and velocities of boids
+pub mod spawn_distribution;
+/// Species manifest listing the models, counts and roles of every species
+pub mod species;
+/// Fixed timestep schedule and transform interpolation
+pub mod simulation_schedule;
+mod utils;
+
+pub mod prelude {
+    pub use crate::{
+        boid_config::{BoidConfig, SpeciesConfigs},
+        boid_map::{BoidEntry, BoidMap},
+        bounds::{BoundaryMode, BoundsVolume, WorldBounds},
+        clustering::{ClusterInfo, ClusteringConfig, Clusters},
+        energy::{Energy, EnergyConfig},
+        escape::{EscapePattern, Panic},
+        spawn_distribution::SpawnDistribution,
+        species::{Role, SpeciesManifest},
+        flock::{Boid, Flock, FlockPlugin, Predator},
+        flock_centre::{CentreMode, FlockStatistics, FlockStats},
+        hunting::{HuntingStrategy, Wander},
+        moveable::{Dimensions, MoveablePlugin, Velocity},
+        obstacle::{Obstacle, ObstaclePlugin, ObstacleShape},
+        predation::{CaptureOutcome, KillCount, PredationSettings, PredationStats, PreyCaptured},
+        predator::Territory,
+        population::{ClearBoids, DespawnBoids, RespawnBoids, SpawnBoids, SpawnRegion},
+        rng::SimRng,
+        selected::Selected,
+        simulation_schedule::{InSimulationSchedule, SimulationSettings, SimulationTick},
+        BoidsPlugins, GuiPlugin, PickingPlugin, RenderingPlugin, SimulationPlugin,
+    };
+}
+
+/// All plugins needed to run and view the simulation, to be added alongside `DefaultPlugins`
+pub struct BoidsPlugins;
+
+impl PluginGroup for BoidsPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(SimulationPlugin::default())
+            .add(RenderingPlugin)
+            .add(GuiPlugin)
+            .add(PickingPlugin)
+    }
+}
+
+/// The flocking model: fixed timestep schedule, random number generation, boid rules and movement
+#[derive(Default)]
+pub struct SimulationPlugin {
+    /// Seed for [`rng::SimRng`], a random seed is used when `None`
+    pub seed: Option<u64>,
+    /// Species to spawn, loaded from [`species::DEFAULT_MANIFEST_PATH`] when `None`
+    pub species: Option<species::SpeciesManifest>,
+    /// Initial behaviour parameters, overriding those given in the species manifest
+    pub config: Option<boid_config::SpeciesConfigs>,
+    /// Whether boids move in 3D or are kept on a plane
+    pub dimensions: moveable::Dimensions,
+    /// How boids are placed on startup
+    pub spawn: spawn_distribution::SpawnDistribution,
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let manifest = self.species.clone().unwrap_or_else(species::SpeciesManifest::load_default);
+        let mut configs = manifest.configs();
+        if let Some(config) = &self.config {
+            configs.merge(config.clone());
+        }
+        app.insert_resource(manifest)
+            .insert_resource(configs)
+            .insert_resource(self.dimensions)
+            .insert_resource(self.spawn.clone())
+            .add_plugins(simulation_schedule::SimulationSchedulePlugin)
+            .add_plugins(rng::RngPlugin { seed: self.seed })
+            .add_plugins(flock::FlockPlugin)
+            .add_plugins(moveable::MoveablePlugin)
+            .add_plugins(population::PopulationPlugin)
+            .add_plugins(obstacle::ObstaclePlugin)
+            .add_plugins(bounds::BoundsPlugin)
+            .add_plugins(predator::PredatorPlugin)
+            .add_plugins(escape::EscapePlugin)
+            .add_plugins(predation::PredationPlugin)
+            .add_plugins(energy::EnergyPlugin)
+            .add_plugins(clustering::ClusteringPlugin);
+    }
+}
+
+/// Models, animations, lighting, camera, debug shapes and the world boundary. Requires `DefaultPlugins`
+pub struct RenderingPlugin;
+
+impl Plugin for RenderingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(WireframePlugin)
+            .add_plugins(lighting::LightingPlugin)
+            .add_plugins(asset_loader::AssetLoaderPlugin)
+            .add_plugins(camera::CameraPlugin)
+            .add_plugins(debug::DebugPlugin);
+    }
+}
+
+/// Configuration, population, obstacle and selection windows, config file hot reloading and FPS counter.
+/// Adds `EguiPlugin` if the app does not have it yet
+pub struct GuiPlugin;
+
+impl Plugin for GuiPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.add_plugins(fps::FpsPlugin)
+            .add_plugins(presets::PresetsPlugin)
+            .add_plugins(config_watcher::ConfigWatcherPlugin)
+            .add_plugins(population_gui::PopulationGuiPlugin)
+            .add_plugins(obstacle_gui::ObstacleGuiPlugin)
+            .add_plugins(selected_gui::SelectedGuiPlugin)
+            .add_plugins(config_gui::ConfigGuiPlugin);
+    }
+}
+
+/// Click to select boids. Adds `DefaultPickingPlugins` if the app does not have them yet
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<CorePlugin>() {
+            app.add_plugins(DefaultPickingPlugins);
+        }
+        app.add_plugins(selected::SelectedPlugin);
+    }
+}
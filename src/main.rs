@@ -1,34 +1,47 @@
-use bevy::{pbr::wireframe::WireframePlugin, prelude::*};
-use bevy_egui::EguiPlugin;
-use bevy_mod_picking::prelude::*;
+use bevy::prelude::*;
+use boids::{config_watcher::WatchedConfig, headless, moveable::Dimensions, presets::ConfigFile, spawn_distribution::{InitialState, SpawnDistribution}, species::SpeciesManifest, BoidsPlugins, SimulationPlugin};
 
-mod lighting;
-mod asset_loader;
-mod camera;
-mod flock;
-mod debug;
-mod moveable;
-mod fps;
-mod simulation_schedule;
-mod selected;
-mod config_gui;
-mod utils;
+mod cli;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugins(DefaultPickingPlugins)
-        .add_plugins(WireframePlugin)
-        .add_plugins(EguiPlugin)
-        .add_plugins(lighting::LightingPlugin)
-        .add_plugins(asset_loader::AssetLoaderPlugin)
-        .add_plugins(camera::CameraPlugin)
-        .add_plugins(flock::FlockPlugin)
-        .add_plugins(moveable::MoveablePlugin)
-        .add_plugins(fps::FpsPlugin)
-        .add_plugins(simulation_schedule::SimulationSchedulePlugin)
-        .add_plugins(config_gui::ConfigGuiPlugin)
-        .add_plugins(selected::SelectedPlugin)
-        .add_plugins(debug::DebugPlugin)
+    let args = cli::CliArgs::parse();
+    let config_file = args.config.as_ref().map(|path| {
+        ConfigFile::load(path).unwrap_or_else(|e| panic!("Failed to load config '{}': {}", path.display(), e))
+    });
+    let species = args.species.as_ref().map(|path| {
+        SpeciesManifest::load(path).unwrap_or_else(|e| panic!("Failed to load species '{}': {}", path.display(), e))
+    });
+    let spawn = config_file.as_ref().and_then(|file| file.spawn.clone()).unwrap_or_default();
+    // Failing to load is reported when spawning, where the state falls back to a uniform spawn
+    let state_seed = match &spawn {
+        SpawnDistribution::File { path } => InitialState::load(path).ok().and_then(|state| state.seed),
+        _ => None,
+    };
+    // A seed given on the command line takes priority over the one recorded in the config, and
+    // that over the one recorded in the initial state
+    let simulation = SimulationPlugin {
+        seed: args.seed.or(config_file.as_ref().and_then(|file| file.seed)).or(state_seed),
+        species,
+        spawn,
+        config: config_file.map(|file| file.configs()),
+        dimensions: if args.two_d { Dimensions::Two } else { Dimensions::Three },
+    };
+
+    if args.headless {
+        let mut app = headless::headless_app(simulation);
+        headless::run_ticks(&mut app, args.ticks);
+        headless::print_summary(&mut app.world);
+        return;
+    }
+    let mut app = App::new();
+    if let Some(path) = &args.config {
+        // Edits to the config file are applied while the viewer runs
+        app.insert_resource(WatchedConfig::new(path));
+    }
+    app.add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes_override: Some(true),
+            ..default()
+        }))
+        .add_plugins(BoidsPlugins.set(simulation))
         .run();
 }
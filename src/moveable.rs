@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::simulation_schedule::InSimulationSchedule;
+use crate::simulation_schedule::{InSimulationSchedule, TransformInterpolation};
 
 #[derive(Component, Debug)]
 pub struct Velocity {
@@ -13,31 +13,66 @@ impl Velocity {
     }
 }
 
+/// Whether entities move freely, or are kept on the horizontal plane through the origin
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dimensions {
+    Two,
+    #[default]
+    Three,
+}
+
+impl Dimensions {
+    // Projects a position, velocity or force onto the plane in 2D
+    pub fn constrain(&self, vector: Vec3) -> Vec3 {
+        match self {
+            Dimensions::Two => Vec3::new(vector.x, 0.0, vector.z),
+            Dimensions::Three => vector,
+        }
+    }
+}
+
+/// Moving entities with this keep their orientation instead of turning to face their velocity
+#[derive(Component, Debug, Default)]
+pub struct FixedOrientation;
+
+// Models are attached separately by the asset loader, so the simulation can run without a renderer
 #[derive(Bundle)]
 pub struct MoveableObjectBundle {
     pub velocity: Velocity,
-    pub model: SceneBundle,
+    pub interpolation: TransformInterpolation,
+    pub spatial: SpatialBundle,
 }
 
 pub struct MoveablePlugin;
 
 impl Plugin for MoveablePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.init_resource::<Dimensions>()
+            .add_systems(FixedUpdate, (
             move_objects,
             face_direction,
-        ).in_set(InSimulationSchedule::EntityUpdates));
+        ).chain().in_set(InSimulationSchedule::Movement));
     }
 }
 
-fn move_objects(time: Res<Time>, mut query: Query<(&Velocity, &mut Transform)>) {
-    for (velocity, mut transform) in query.iter_mut() {
+pub fn move_objects(time: Res<Time>, dimensions: Res<Dimensions>, mut query: Query<(&mut Velocity, &mut Transform)>) {
+    for (mut velocity, mut transform) in query.iter_mut() {
+        if *dimensions == Dimensions::Two {
+            // rules may push boids off the plane, which is undone here once every rule has run
+            velocity.value = dimensions.constrain(velocity.value);
+            transform.translation = dimensions.constrain(transform.translation);
+        }
         transform.translation += velocity.value * time.delta_seconds();
     }
 }
 
-fn face_direction(mut query: Query<(&Velocity, &mut Transform)>) {
+pub fn face_direction(mut query: Query<(&Velocity, &mut Transform), Without<FixedOrientation>>) {
     for (velocity, mut transform) in query.iter_mut() {
-        transform.look_to(-velocity.value, Vec3::Y);
+        let Some(forward) = velocity.value.try_normalize() else {
+            continue;
+        };
+        // heading straight up or down the world up gives no roll, so keep the current one
+        let up = if forward.cross(Vec3::Y).length_squared() < 1e-6 { transform.up() } else { Vec3::Y };
+        transform.look_to(-forward, up);
     }
 }
\ No newline at end of file
@@ -0,0 +1,370 @@
+use bevy::{prelude::*, render::mesh::{Indices, VertexAttributeValues}};
+
+use crate::{
+    boid_config::SpeciesConfigs,
+    flock::{bound_vector, predator_prey_rules, Boid},
+    moveable::{FixedOrientation, MoveableObjectBundle, Velocity},
+    simulation_schedule::{InSimulationSchedule, TransformInterpolation},
+};
+
+/// Shape of an obstacle in its local space, centred on its transform. Scale is ignored
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObstacleShape {
+    Sphere { radius: f32 },
+    Box { half_extents: Vec3 },
+    // Along the local y axis, `half_length` being the distance from the centre to the centre of either cap
+    Capsule { radius: f32, half_length: f32 },
+    // Triangle mesh. Only seen by the look-ahead ray, so boids that end up inside are not pushed out
+    Mesh { triangles: Vec<[Vec3; 3]> },
+}
+
+impl ObstacleShape {
+    /// Triangles of a triangle list mesh, `None` if it has no positions or another topology
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        if mesh.primitive_topology() != bevy::render::render_resource::PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+        let vertex = |i: usize| Vec3::from(positions[i]);
+        let triangles = match mesh.indices() {
+            Some(indices) => {
+                let indices: Vec<usize> = match indices {
+                    Indices::U16(indices) => indices.iter().map(|&i| i as usize).collect(),
+                    Indices::U32(indices) => indices.iter().map(|&i| i as usize).collect(),
+                };
+                indices.chunks_exact(3).map(|t| [vertex(t[0]), vertex(t[1]), vertex(t[2])]).collect()
+            }
+            None => (0..positions.len() / 3).map(|t| [vertex(t * 3), vertex(t * 3 + 1), vertex(t * 3 + 2)]).collect(),
+        };
+        Some(ObstacleShape::Mesh { triangles })
+    }
+
+    // Radius of a sphere around the centre containing the whole shape
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            ObstacleShape::Sphere { radius } => *radius,
+            ObstacleShape::Box { half_extents } => half_extents.length(),
+            ObstacleShape::Capsule { radius, half_length } => radius + half_length,
+            ObstacleShape::Mesh { triangles } => triangles.iter().flatten().map(|v| v.length()).fold(0.0, f32::max),
+        }
+    }
+
+    /// Distance to where a ray from outside the shape first hits it, and the surface normal there.
+    /// `direction` must be normalised
+    pub fn ray_hit(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<(f32, Vec3)> {
+        let hit = match self {
+            ObstacleShape::Sphere { radius } => {
+                ray_sphere(origin, direction, Vec3::ZERO, *radius).map(|t| (t, (origin + direction * t).normalize_or_zero()))
+            }
+            ObstacleShape::Box { half_extents } => ray_box(origin, direction, *half_extents),
+            ObstacleShape::Capsule { radius, half_length } => {
+                let (a, b) = (Vec3::NEG_Y * *half_length, Vec3::Y * *half_length);
+                // a capsule is the union of a cylinder and a sphere at either end
+                let mut nearest = [ray_sphere(origin, direction, a, *radius), ray_sphere(origin, direction, b, *radius)]
+                    .into_iter()
+                    .flatten()
+                    .fold(f32::MAX, f32::min);
+                let (ox, oz, dx, dz) = (origin.x, origin.z, direction.x, direction.z);
+                let qa = dx * dx + dz * dz;
+                let qb = ox * dx + oz * dz;
+                let qc = ox * ox + oz * oz - radius * radius;
+                let discriminant = qb * qb - qa * qc;
+                if qa > f32::EPSILON && discriminant >= 0.0 {
+                    let t = (-qb - discriminant.sqrt()) / qa;
+                    if t >= 0.0 && (origin.y + direction.y * t).abs() <= *half_length {
+                        nearest = nearest.min(t);
+                    }
+                }
+                (nearest < f32::MAX).then(|| {
+                    let point = origin + direction * nearest;
+                    let axis = Vec3::Y * point.y.clamp(-half_length, *half_length);
+                    (nearest, (point - axis).normalize_or_zero())
+                })
+            }
+            ObstacleShape::Mesh { triangles } => triangles.iter()
+                .filter_map(|triangle| ray_triangle(origin, direction, triangle))
+                .min_by(|a, b| a.0.total_cmp(&b.0)),
+        };
+        hit.filter(|(t, _)| *t <= max_distance)
+    }
+
+    /// Shortest way out of the shape for a point inside it
+    pub fn escape_direction(&self, point: Vec3) -> Option<Vec3> {
+        match self {
+            ObstacleShape::Sphere { radius } => {
+                (point.length() < *radius).then(|| point.try_normalize().unwrap_or(Vec3::Y))
+            }
+            ObstacleShape::Box { half_extents } => {
+                let depth = *half_extents - point.abs();
+                if depth.min_element() <= 0.0 {
+                    return None;
+                }
+                // leave through the nearest face
+                let axis = if depth.x <= depth.y && depth.x <= depth.z {
+                    Vec3::X
+                } else if depth.y <= depth.z {
+                    Vec3::Y
+                } else {
+                    Vec3::Z
+                };
+                Some(axis * if point.dot(axis) < 0.0 { -1.0 } else { 1.0 })
+            }
+            ObstacleShape::Capsule { radius, half_length } => {
+                let offset = point - Vec3::Y * point.y.clamp(-half_length, *half_length);
+                (offset.length() < *radius).then(|| offset.try_normalize().unwrap_or(Vec3::X))
+            }
+            ObstacleShape::Mesh { .. } => None,
+        }
+    }
+
+    /// Mesh for drawing the obstacle
+    pub fn mesh(&self) -> Mesh {
+        match self {
+            ObstacleShape::Sphere { radius } => shape::UVSphere { radius: *radius, ..default() }.into(),
+            ObstacleShape::Box { half_extents } => shape::Box::new(half_extents.x * 2.0, half_extents.y * 2.0, half_extents.z * 2.0).into(),
+            ObstacleShape::Capsule { radius, half_length } => shape::Capsule { radius: *radius, depth: half_length * 2.0, ..default() }.into(),
+            ObstacleShape::Mesh { triangles } => {
+                let positions: Vec<[f32; 3]> = triangles.iter().flatten().map(|v| v.to_array()).collect();
+                let normals: Vec<[f32; 3]> = triangles.iter()
+                    .flat_map(|[a, b, c]| [(*b - *a).cross(*c - *a).normalize_or_zero().to_array(); 3])
+                    .collect();
+                Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList)
+                    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+                    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+            }
+        }
+    }
+}
+
+fn ray_sphere(origin: Vec3, direction: Vec3, centre: Vec3, radius: f32) -> Option<f32> {
+    let offset = origin - centre;
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let t = -b - discriminant.sqrt();
+    (t >= 0.0).then_some(t)
+}
+
+fn ray_box(origin: Vec3, direction: Vec3, half_extents: Vec3) -> Option<(f32, Vec3)> {
+    let mut near = f32::MIN;
+    let mut far = f32::MAX;
+    let mut normal = Vec3::ZERO;
+    for (axis, unit) in [Vec3::X, Vec3::Y, Vec3::Z].into_iter().enumerate() {
+        let (o, d, h) = (origin[axis], direction[axis], half_extents[axis]);
+        if d.abs() < f32::EPSILON {
+            if o.abs() > h {
+                return None;
+            }
+            continue;
+        }
+        let (t1, t2) = ((-h - o) / d, (h - o) / d);
+        if t1.min(t2) > near {
+            near = t1.min(t2);
+            normal = unit * -d.signum();
+        }
+        far = far.min(t1.max(t2));
+    }
+    (near <= far && near >= 0.0).then_some((near, normal))
+}
+
+fn ray_triangle(origin: Vec3, direction: Vec3, [a, b, c]: &[Vec3; 3]) -> Option<(f32, Vec3)> {
+    let (edge1, edge2) = (*b - *a, *c - *a);
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let offset = origin - *a;
+    let u = offset.dot(p) / determinant;
+    let q = offset.cross(edge1);
+    let v = direction.dot(q) / determinant;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) / determinant;
+    // the normal faces the ray, whichever way the triangle is wound
+    let normal = edge1.cross(edge2).normalize_or_zero();
+    (t >= 0.0).then_some((t, if normal.dot(direction) > 0.0 { -normal } else { normal }))
+}
+
+/// Something boids steer around. Obstacles with a non-zero `Velocity` move like any other entity
+#[derive(Component, Debug, Clone)]
+pub struct Obstacle {
+    shape: ObstacleShape,
+    // Kept with the shape, as it walks every vertex of a mesh
+    bounding_radius: f32,
+}
+
+impl Obstacle {
+    pub fn new(shape: ObstacleShape) -> Self {
+        Self { bounding_radius: shape.bounding_radius(), shape }
+    }
+
+    pub fn shape(&self) -> &ObstacleShape {
+        &self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: ObstacleShape) {
+        *self = Self::new(shape);
+    }
+
+    pub fn bounding_radius(&self) -> f32 {
+        self.bounding_radius
+    }
+}
+
+pub fn spawn_obstacle(commands: &mut Commands, shape: ObstacleShape, transform: Transform, velocity: Vec3) -> Entity {
+    commands.spawn((
+        MoveableObjectBundle {
+            velocity: Velocity::new(velocity),
+            interpolation: TransformInterpolation::new(transform),
+            spatial: SpatialBundle::from_transform(transform),
+        },
+        Obstacle::new(shape),
+        FixedOrientation,
+    )).id()
+}
+
+pub struct ObstaclePlugin;
+
+impl Plugin for ObstaclePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, avoid_obstacles
+            .after(predator_prey_rules)
+            .in_set(InSimulationSchedule::EntityUpdates));
+    }
+}
+
+pub fn avoid_obstacles(
+    mut boids: Query<(&Transform, &mut Velocity, &Boid), Without<Obstacle>>,
+    obstacles: Query<(&Obstacle, &Transform, &Velocity)>,
+    configs: Res<SpeciesConfigs>,
+    time: Res<Time>,
+) {
+    if obstacles.is_empty() {
+        return;
+    }
+    boids.par_iter_mut().for_each(|(transform, mut velocity, boid)| {
+        let config = configs.get(&boid.species);
+        let look_ahead = config.obstacle_avoidance_distance;
+        let mut escape = Vec3::ZERO;
+        // distance to the nearest hit along the look-ahead ray, the surface normal and the ray direction
+        let mut nearest: Option<(f32, Vec3, Vec3)> = None;
+        for (obstacle, obstacle_transform, obstacle_velocity) in obstacles.iter() {
+            let offset = transform.translation - obstacle_transform.translation;
+            if offset.length() > look_ahead + obstacle.bounding_radius() {
+                continue;
+            }
+            let to_local = obstacle_transform.rotation.inverse();
+            let local = to_local * offset;
+            if let Some(out) = obstacle.shape().escape_direction(local) {
+                escape += obstacle_transform.rotation * out;
+                continue;
+            }
+            // look ahead along the velocity relative to the obstacle, so moving obstacles are avoided where they will be
+            let Some(direction) = (velocity.value - obstacle_velocity.value).try_normalize() else {
+                continue;
+            };
+            if let Some((distance, normal)) = obstacle.shape().ray_hit(local, to_local * direction, look_ahead) {
+                if nearest.is_none_or(|(nearest_distance, ..)| distance < nearest_distance) {
+                    nearest = Some((distance, obstacle_transform.rotation * normal, direction));
+                }
+            }
+        }
+
+        let mut force = escape.normalize_or_zero() * config.obstacle_avoidance_strength;
+        if let Some((distance, normal, direction)) = nearest {
+            // turn sideways, away from the surface, harder the closer the hit
+            let mut side = normal.reject_from_normalized(direction);
+            if side.length_squared() < f32::EPSILON {
+                side = direction.any_orthonormal_vector();
+            }
+            force += side.normalize() * config.obstacle_avoidance_strength * (1.0 - distance / look_ahead);
+        }
+        if force != Vec3::ZERO {
+            velocity.value = bound_vector(velocity.value + force * time.delta_seconds(), config.min_speed, config.max_speed);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_hit(hit: Option<(f32, Vec3)>, distance: f32, normal: Vec3) {
+        let (t, n) = hit.expect("expected a hit");
+        assert!((t - distance).abs() < 1e-4, "distance {} != {}", t, distance);
+        assert!(n.abs_diff_eq(normal, 1e-4), "normal {} != {}", n, normal);
+    }
+
+    #[test]
+    fn ray_sphere_hits_the_near_side() {
+        let t = ray_sphere(Vec3::new(-5.0, 0.0, 0.0), Vec3::X, Vec3::new(1.0, 0.0, 0.0), 2.0).unwrap();
+        assert!((t - 4.0).abs() < 1e-4);
+        // pointing away, passing by and starting inside
+        assert_eq!(ray_sphere(Vec3::new(-5.0, 0.0, 0.0), Vec3::NEG_X, Vec3::ZERO, 2.0), None);
+        assert_eq!(ray_sphere(Vec3::new(-5.0, 3.0, 0.0), Vec3::X, Vec3::ZERO, 2.0), None);
+        assert_eq!(ray_sphere(Vec3::ZERO, Vec3::X, Vec3::ZERO, 2.0), None);
+    }
+
+    #[test]
+    fn ray_box_returns_the_face_normal() {
+        let half_extents = Vec3::new(1.0, 2.0, 3.0);
+        assert_hit(ray_box(Vec3::new(-5.0, 0.5, 0.5), Vec3::X, half_extents), 4.0, Vec3::NEG_X);
+        assert_hit(ray_box(Vec3::new(0.0, 0.0, 10.0), Vec3::NEG_Z, half_extents), 7.0, Vec3::Z);
+        let diagonal = Vec3::new(1.0, 1.0, 0.0).normalize();
+        assert_hit(ray_box(Vec3::new(-2.0, -4.0, 0.0), diagonal, half_extents), 2.0 * 2f32.sqrt(), Vec3::NEG_Y);
+        // parallel to a face outside the slab, pointing away and starting inside
+        assert_eq!(ray_box(Vec3::new(-5.0, 2.5, 0.0), Vec3::X, half_extents), None);
+        assert_eq!(ray_box(Vec3::new(-5.0, 0.0, 0.0), Vec3::NEG_X, half_extents), None);
+        assert_eq!(ray_box(Vec3::ZERO, Vec3::X, half_extents), None);
+    }
+
+    #[test]
+    fn ray_triangle_normal_faces_the_ray() {
+        let triangle = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0)];
+        assert_hit(ray_triangle(Vec3::new(0.5, 0.5, 3.0), Vec3::NEG_Z, &triangle), 3.0, Vec3::Z);
+        assert_hit(ray_triangle(Vec3::new(0.5, 0.5, -3.0), Vec3::Z, &triangle), 3.0, Vec3::NEG_Z);
+        // outside the edges, behind the origin and parallel to the plane
+        assert_eq!(ray_triangle(Vec3::new(1.5, 1.5, 3.0), Vec3::NEG_Z, &triangle), None);
+        assert_eq!(ray_triangle(Vec3::new(0.5, 0.5, 3.0), Vec3::Z, &triangle), None);
+        assert_eq!(ray_triangle(Vec3::new(0.5, 0.5, 3.0), Vec3::X, &triangle), None);
+    }
+
+    #[test]
+    fn capsule_ray_hits_the_side_and_caps() {
+        let capsule = ObstacleShape::Capsule { radius: 1.0, half_length: 2.0 };
+        // the cylinder
+        assert_hit(capsule.ray_hit(Vec3::new(-5.0, 1.0, 0.0), Vec3::X, 10.0), 4.0, Vec3::NEG_X);
+        // along the axis into either cap
+        assert_hit(capsule.ray_hit(Vec3::new(0.0, 10.0, 0.0), Vec3::NEG_Y, 10.0), 7.0, Vec3::Y);
+        assert_hit(capsule.ray_hit(Vec3::new(0.0, -10.0, 0.0), Vec3::Y, 10.0), 7.0, Vec3::NEG_Y);
+        // beside the cylinder's end, only hitting the rounded cap
+        let (t, normal) = capsule.ray_hit(Vec3::new(-5.0, 2.5, 0.0), Vec3::X, 10.0).unwrap();
+        assert!((t - (5.0 - 0.75f32.sqrt())).abs() < 1e-4);
+        assert!(normal.abs_diff_eq(Vec3::new(-(0.75f32.sqrt()), 0.5, 0.0), 1e-4));
+        // past the caps, missing the side and out of range
+        assert_eq!(capsule.ray_hit(Vec3::new(-5.0, 3.5, 0.0), Vec3::X, 10.0), None);
+        assert_eq!(capsule.ray_hit(Vec3::new(-5.0, 0.0, 1.5), Vec3::X, 10.0), None);
+        assert_eq!(capsule.ray_hit(Vec3::new(-5.0, 0.0, 0.0), Vec3::X, 3.0), None);
+    }
+
+    #[test]
+    fn escape_direction_leaves_by_the_nearest_surface() {
+        let capsule = ObstacleShape::Capsule { radius: 1.0, half_length: 2.0 };
+        assert_eq!(capsule.escape_direction(Vec3::new(0.5, 1.5, 0.0)), Some(Vec3::X));
+        assert_eq!(capsule.escape_direction(Vec3::new(0.0, 2.5, 0.0)), Some(Vec3::Y));
+        assert_eq!(capsule.escape_direction(Vec3::new(0.0, 3.5, 0.0)), None);
+        assert_eq!(capsule.escape_direction(Vec3::new(1.5, 0.0, 0.0)), None);
+
+        let cuboid = ObstacleShape::Box { half_extents: Vec3::new(1.0, 2.0, 3.0) };
+        assert_eq!(cuboid.escape_direction(Vec3::new(0.0, -1.5, 0.0)), Some(Vec3::NEG_Y));
+        assert_eq!(cuboid.escape_direction(Vec3::new(0.5, 0.0, 2.9)), Some(Vec3::Z));
+        assert_eq!(cuboid.escape_direction(Vec3::new(0.0, 0.0, 3.5)), None);
+    }
+}
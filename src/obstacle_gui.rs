@@ -0,0 +1,209 @@
+use bevy::{asset::LoadState, prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    camera,
+    config_gui::vec3_ui,
+    moveable::Velocity,
+    obstacle::{spawn_obstacle, Obstacle, ObstacleShape},
+};
+
+pub struct ObstacleGuiPlugin;
+
+impl Plugin for ObstacleGuiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ObstacleGuiState>()
+            .add_systems(Update, (obstacle_egui, place_obstacle_at_cursor, build_mesh_obstacles).chain());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ShapeKind {
+    Sphere,
+    Box,
+    Capsule,
+    Mesh,
+}
+
+#[derive(Resource)]
+struct ObstacleGuiState {
+    kind: ShapeKind,
+    radius: f32,
+    half_extents: Vec3,
+    half_length: f32,
+    // Asset path of a mesh, such as a primitive of a glTF model, and how much to scale it by
+    mesh_path: String,
+    mesh_scale: f32,
+    velocity: Vec3,
+    // The next click in the scene places an obstacle at the cursor
+    placing: bool,
+}
+
+impl Default for ObstacleGuiState {
+    fn default() -> Self {
+        Self {
+            kind: ShapeKind::Sphere,
+            radius: 50.0,
+            half_extents: Vec3::splat(50.0),
+            half_length: 50.0,
+            mesh_path: "Whale.glb#Mesh0/Primitive0".to_string(),
+            mesh_scale: 10.0,
+            velocity: Vec3::ZERO,
+            placing: false,
+        }
+    }
+}
+
+impl ObstacleGuiState {
+    // Shape of the next obstacle, `None` for a mesh, which has to be loaded first
+    fn shape(&self) -> Option<ObstacleShape> {
+        match self.kind {
+            ShapeKind::Sphere => Some(ObstacleShape::Sphere { radius: self.radius }),
+            ShapeKind::Box => Some(ObstacleShape::Box { half_extents: self.half_extents }),
+            ShapeKind::Capsule => Some(ObstacleShape::Capsule { radius: self.radius, half_length: self.half_length }),
+            ShapeKind::Mesh => None,
+        }
+    }
+
+    fn place(&self, commands: &mut Commands, asset_server: &AssetServer, position: Vec3) {
+        let transform = Transform::from_translation(position);
+        match self.shape() {
+            Some(shape) => {
+                spawn_obstacle(commands, shape, transform, self.velocity);
+            }
+            None => {
+                commands.spawn(PendingMeshObstacle {
+                    mesh: asset_server.load(&self.mesh_path),
+                    scale: self.mesh_scale,
+                    transform,
+                    velocity: self.velocity,
+                });
+            }
+        }
+    }
+}
+
+// A mesh obstacle waiting for its mesh to load
+#[derive(Component)]
+struct PendingMeshObstacle {
+    mesh: Handle<Mesh>,
+    scale: f32,
+    transform: Transform,
+    velocity: Vec3,
+}
+
+fn obstacle_egui(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    asset_server: Res<AssetServer>,
+    mut state: ResMut<ObstacleGuiState>,
+    obstacles: Query<(Entity, &Obstacle, &Transform, &Velocity)>,
+) {
+    egui::Window::new("Obstacles").default_open(false).show(contexts.ctx_mut(), |ui| {
+        egui::ComboBox::from_label("Shape")
+            .selected_text(format!("{:?}", state.kind))
+            .show_ui(ui, |ui| {
+                for kind in [ShapeKind::Sphere, ShapeKind::Box, ShapeKind::Capsule, ShapeKind::Mesh] {
+                    ui.selectable_value(&mut state.kind, kind, format!("{:?}", kind));
+                }
+            });
+        match state.kind {
+            ShapeKind::Sphere => {
+                ui.add(egui::Slider::new(&mut state.radius, 1.0..=500.0).text("Radius"));
+            }
+            ShapeKind::Box => vec3_ui(ui, &mut state.half_extents, "Half Extents"),
+            ShapeKind::Capsule => {
+                ui.add(egui::Slider::new(&mut state.radius, 1.0..=500.0).text("Radius"));
+                ui.add(egui::Slider::new(&mut state.half_length, 0.0..=500.0).text("Half Length"));
+            }
+            ShapeKind::Mesh => {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut state.mesh_path);
+                    ui.label("Mesh");
+                });
+                ui.add(egui::Slider::new(&mut state.mesh_scale, 0.1..=500.0).logarithmic(true).text("Scale"));
+            }
+        }
+        vec3_ui(ui, &mut state.velocity, "Velocity");
+
+        ui.horizontal(|ui| {
+            if ui.button("Place at Origin").clicked() {
+                state.place(&mut commands, &asset_server, Vec3::ZERO);
+            }
+            let label = if state.placing { "Click in the Scene..." } else { "Place at Cursor" };
+            if ui.button(label).clicked() {
+                state.placing = !state.placing;
+            }
+        });
+        ui.separator();
+
+        for (entity, obstacle, transform, velocity) in obstacles.iter() {
+            ui.horizontal(|ui| {
+                let kind = match obstacle.shape() {
+                    ObstacleShape::Sphere { .. } => "Sphere",
+                    ObstacleShape::Box { .. } => "Box",
+                    ObstacleShape::Capsule { .. } => "Capsule",
+                    ObstacleShape::Mesh { .. } => "Mesh",
+                };
+                let position = transform.translation;
+                ui.label(format!("{} at ({:.0}, {:.0}, {:.0}), speed {:.1}", kind, position.x, position.y, position.z, velocity.value.length()));
+                if ui.button("Remove").clicked() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            });
+        }
+        if ui.button("Clear Obstacles").clicked() {
+            for (entity, ..) in obstacles.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    });
+}
+
+fn place_obstacle_at_cursor(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    asset_server: Res<AssetServer>,
+    mut state: ResMut<ObstacleGuiState>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<camera::Camera>>,
+) {
+    if !state.placing || !mouse.just_pressed(MouseButton::Left) || contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single()) else {
+        return;
+    };
+    let Some(position) = camera::cursor_on_ground(window, camera, camera_transform) else {
+        return;
+    };
+    state.place(&mut commands, &asset_server, position);
+    state.placing = false;
+}
+
+fn build_mesh_obstacles(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    meshes: Res<Assets<Mesh>>,
+    pending: Query<(Entity, &PendingMeshObstacle)>,
+) {
+    for (entity, obstacle) in pending.iter() {
+        if asset_server.load_state(&obstacle.mesh) == LoadState::Failed {
+            warn!("Could not load obstacle mesh {:?}", obstacle.mesh.path());
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let Some(mesh) = meshes.get(&obstacle.mesh) else {
+            continue;
+        };
+        commands.entity(entity).despawn();
+        match ObstacleShape::from_mesh(mesh) {
+            Some(ObstacleShape::Mesh { triangles }) => {
+                let triangles = triangles.into_iter().map(|triangle| triangle.map(|vertex| vertex * obstacle.scale)).collect();
+                spawn_obstacle(&mut commands, ObstacleShape::Mesh { triangles }, obstacle.transform, obstacle.velocity);
+            }
+            _ => warn!("Obstacle mesh {:?} is not a triangle list", obstacle.mesh.path()),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    boid_config::SpeciesConfigs,
+    flock::{random_velocity, spawn_boid, spawn_manifest, Boid, SpawnOrder},
+    moveable::Dimensions,
+    rng::SimRng,
+    simulation_schedule::InSimulationSchedule,
+    spawn_distribution::SpawnDistribution,
+    species::SpeciesManifest,
+};
+
+/// Where newly spawned boids are placed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpawnRegion {
+    // Within a horizontal disc around a point
+    Around { centre: Vec3, radius: f32 },
+    // Within an axis aligned box between two opposite corners
+    Box { min: Vec3, max: Vec3 },
+}
+
+impl SpawnRegion {
+    pub fn sample(&self, rng: &mut SimRng) -> Vec3 {
+        match *self {
+            SpawnRegion::Around { centre, radius } => {
+                // uniform over the disc area
+                let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+                let distance = radius * rng.gen::<f32>().sqrt();
+                centre + Vec3::new(angle.cos(), 0.0, angle.sin()) * distance
+            }
+            // also fine with `min` and `max` the wrong way round on some axes
+            SpawnRegion::Box { min, max } => min + Vec3::new(rng.gen(), rng.gen(), rng.gen()) * (max - min),
+        }
+    }
+}
+
+/// Adds `count` boids of a species from the manifest
+#[derive(Event, Debug, Clone)]
+pub struct SpawnBoids {
+    pub species: String,
+    pub count: usize,
+    pub region: SpawnRegion,
+}
+
+/// Removes up to `count` boids of a species, most recently spawned first
+#[derive(Event, Debug, Clone)]
+pub struct DespawnBoids {
+    pub species: String,
+    pub count: usize,
+}
+
+/// Removes every boid
+#[derive(Event, Debug, Clone)]
+pub struct ClearBoids;
+
+/// Removes every boid and spawns the species manifest again with the current `SpawnDistribution`
+#[derive(Event, Debug, Clone)]
+pub struct RespawnBoids;
+
+pub struct PopulationPlugin;
+
+impl Plugin for PopulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnBoids>()
+            .add_event::<DespawnBoids>()
+            .add_event::<ClearBoids>()
+            .add_event::<RespawnBoids>()
+            .add_systems(Update, (
+                handle_clear_boids,
+                handle_despawn_boids,
+                handle_spawn_boids,
+                handle_respawn_boids,
+            ).chain().in_set(InSimulationSchedule::UserInput));
+    }
+}
+
+fn handle_spawn_boids(
+    mut commands: Commands,
+    mut events: EventReader<SpawnBoids>,
+    manifest: Res<SpeciesManifest>,
+    configs: Res<SpeciesConfigs>,
+    dimensions: Res<Dimensions>,
+    mut rng: ResMut<SimRng>,
+) {
+    for event in events.read() {
+        let Some(identity) = manifest.species.iter().position(|species| species.name == event.species) else {
+            warn!("Cannot spawn unknown species '{}'", event.species);
+            continue;
+        };
+        let species = &manifest.species[identity];
+        for _ in 0..event.count {
+            let transform = Transform::from_translation(dimensions.constrain(event.region.sample(&mut rng)));
+            let velocity = random_velocity(&mut rng, configs.get(&species.name).min_speed, *dimensions);
+            spawn_boid(&mut commands, species, identity, transform, velocity);
+        }
+    }
+}
+
+fn handle_despawn_boids(mut commands: Commands, mut events: EventReader<DespawnBoids>, boids: Query<(Entity, &Boid, &SpawnOrder)>) {
+    // despawning is deferred, so keep track of boids already removed by an earlier event this frame
+    let mut despawned = Vec::new();
+    for event in events.read() {
+        let mut entities: Vec<(SpawnOrder, Entity)> = boids.iter()
+            .filter(|(entity, boid, _)| boid.species == event.species && !despawned.contains(entity))
+            .map(|(entity, _, order)| (*order, entity))
+            .collect();
+        entities.sort();
+        for (_, entity) in entities.into_iter().rev().take(event.count) {
+            commands.entity(entity).despawn_recursive();
+            despawned.push(entity);
+        }
+    }
+}
+
+fn handle_clear_boids(mut commands: Commands, mut events: EventReader<ClearBoids>, boids: Query<Entity, With<Boid>>) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for entity in boids.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_respawn_boids(
+    mut commands: Commands,
+    mut events: EventReader<RespawnBoids>,
+    manifest: Res<SpeciesManifest>,
+    configs: Res<SpeciesConfigs>,
+    dimensions: Res<Dimensions>,
+    distribution: Res<SpawnDistribution>,
+    mut rng: ResMut<SimRng>,
+    boids: Query<Entity, With<Boid>>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for entity in boids.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_manifest(&mut commands, &manifest, &configs, *dimensions, &distribution, &mut rng);
+}
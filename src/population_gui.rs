@@ -0,0 +1,283 @@
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    camera,
+    clustering::{ClusteringConfig, Clusters},
+    flock::Boid,
+    flock_centre::FlockStatistics,
+    config_gui::vec3_ui,
+    moveable::Velocity,
+    predation::{CaptureOutcome, KillCount, PredationSettings, PredationStats},
+    population::{ClearBoids, DespawnBoids, RespawnBoids, SpawnBoids, SpawnRegion},
+    rng::SimRng,
+    simulation_schedule::{SimulationTick, TransformInterpolation},
+    spawn_distribution::{InitialBoid, InitialState, SpawnDistribution},
+    species::SpeciesManifest,
+};
+
+pub struct PopulationGuiPlugin;
+
+impl Plugin for PopulationGuiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PopulationGuiState>()
+            .add_systems(Update, (population_egui, place_at_cursor).chain());
+    }
+}
+
+#[derive(Resource)]
+struct PopulationGuiState {
+    species: usize,
+    count: usize,
+    radius: f32,
+    // The next click in the scene spawns boids at the cursor
+    placing: bool,
+    // Where the current positions and velocities of every boid are saved to
+    state_path: String,
+    state_status: String,
+}
+
+impl Default for PopulationGuiState {
+    fn default() -> Self {
+        Self {
+            species: 0,
+            count: 50,
+            radius: 100.0,
+            placing: false,
+            state_path: "assets/initial_state.ron".to_string(),
+            state_status: String::new(),
+        }
+    }
+}
+
+#[derive(SystemParam)]
+struct PopulationEvents<'w> {
+    spawn: EventWriter<'w, SpawnBoids>,
+    despawn: EventWriter<'w, DespawnBoids>,
+    clear: EventWriter<'w, ClearBoids>,
+    respawn: EventWriter<'w, RespawnBoids>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn population_egui(
+    mut contexts: EguiContexts,
+    mut state: ResMut<PopulationGuiState>,
+    manifest: Res<SpeciesManifest>,
+    statistics: Res<FlockStatistics>,
+    clusters: Res<Clusters>,
+    mut clustering_config: ResMut<ClusteringConfig>,
+    tick: Res<SimulationTick>,
+    predation_stats: Res<PredationStats>,
+    mut predation_settings: ResMut<PredationSettings>,
+    predators: Query<(&Boid, &KillCount)>,
+    mut distribution: ResMut<SpawnDistribution>,
+    boids: Query<(&Boid, &TransformInterpolation, &Velocity)>,
+    rng: Res<SimRng>,
+    mut events: PopulationEvents,
+) {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (boid, ..) in boids.iter() {
+        *counts.entry(boid.species.as_str()).or_default() += 1;
+    }
+
+    egui::Window::new("Population").show(contexts.ctx_mut(), |ui| {
+        for species in &manifest.species {
+            ui.label(format!("{}: {}", species.name, counts.get(species.name.as_str()).unwrap_or(&0)));
+        }
+        ui.collapsing("Flock Statistics", |ui| {
+            for (identity, stats) in statistics.flocks.iter() {
+                let name = manifest.species.get(*identity).map(|species| species.name.as_str()).unwrap_or("Unknown");
+                ui.label(format!(
+                    "{}: centroid ({:.0}, {:.0}, {:.0}), speed {:.1}, spread {:.0}",
+                    name, stats.centroid.x, stats.centroid.y, stats.centroid.z, stats.velocity.length(), stats.spread,
+                ));
+            }
+        });
+        ui.collapsing("Predation", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Caught Prey");
+                ui.selectable_value(&mut predation_settings.outcome, CaptureOutcome::Respawn, "Respawn");
+                ui.selectable_value(&mut predation_settings.outcome, CaptureOutcome::Remove, "Remove");
+            });
+            let name = |identity: &usize| manifest.species.get(*identity).map(|species| species.name.as_str()).unwrap_or("Unknown");
+            for (identity, kills) in predation_stats.kills.iter() {
+                ui.label(format!("{} caught {}", name(identity), kills));
+            }
+            for (identity, losses) in predation_stats.losses.iter() {
+                ui.label(format!("{} lost {}", name(identity), losses));
+            }
+            ui.separator();
+            for (predator, kills) in predators.iter() {
+                ui.label(format!("{}: {} kills", predator.species, kills.0));
+            }
+        });
+        ui.collapsing("Clusters", |ui| {
+            ui.checkbox(&mut clustering_config.enabled, "Detect Clusters");
+            ui.add(egui::Slider::new(&mut clustering_config.link_distance, 1.0..=200.0).text("Link Distance"));
+            ui.add(egui::Slider::new(&mut clustering_config.max_heading_angle, 0.0..=std::f32::consts::PI).text("Max Heading Angle"));
+            ui.add(egui::Slider::new(&mut clustering_config.min_size, 1..=50).text("Min Size"));
+            ui.checkbox(&mut clustering_config.same_species_only, "Same Species Only");
+            ui.label(format!("{} clusters", clusters.clusters.len()));
+            for cluster in clusters.clusters.iter().take(10) {
+                ui.label(format!("#{}: {} boids, {} ticks old", cluster.id, cluster.size, cluster.lifetime(tick.0)));
+            }
+        });
+        ui.collapsing("Initial Conditions", |ui| {
+            spawn_distribution_ui(ui, &mut distribution);
+            if ui.button("Respawn").clicked() {
+                events.respawn.send(RespawnBoids);
+            }
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut state.state_path);
+                if ui.button("Save Current State").clicked() {
+                    let initial_state = InitialState {
+                        seed: Some(rng.seed()),
+                        boids: boids.iter()
+                            // the simulation state, `Transform` is the interpolated pose being drawn
+                            .map(|(boid, interpolation, velocity)| InitialBoid {
+                                species: boid.species.clone(),
+                                position: interpolation.current.translation,
+                                velocity: velocity.value,
+                            })
+                            .collect(),
+                    };
+                    state.state_status = match initial_state.save(&state.state_path) {
+                        Ok(()) => format!("Saved {} boids to {}", initial_state.boids.len(), state.state_path),
+                        Err(e) => format!("Failed to save {}: {}", state.state_path, e),
+                    };
+                }
+            });
+            if !state.state_status.is_empty() {
+                ui.label(&state.state_status);
+            }
+        });
+        ui.separator();
+
+        let Some(species) = manifest.species.get(state.species).map(|species| species.name.clone()) else {
+            return;
+        };
+        egui::ComboBox::from_label("Species")
+            .selected_text(&species)
+            .show_ui(ui, |ui| {
+                for (i, species) in manifest.species.iter().enumerate() {
+                    ui.selectable_value(&mut state.species, i, &species.name);
+                }
+            });
+        ui.add(egui::Slider::new(&mut state.count, 1..=1000).text("Count"));
+        ui.add(egui::Slider::new(&mut state.radius, 0.0..=1000.0).text("Radius"));
+
+        ui.horizontal(|ui| {
+            if ui.button("Spawn at Origin").clicked() {
+                events.spawn.send(SpawnBoids {
+                    species: species.clone(),
+                    count: state.count,
+                    region: SpawnRegion::Around { centre: Vec3::ZERO, radius: state.radius },
+                });
+            }
+            let label = if state.placing { "Click in the Scene..." } else { "Spawn at Cursor" };
+            if ui.button(label).clicked() {
+                state.placing = !state.placing;
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Remove").clicked() {
+                events.despawn.send(DespawnBoids {
+                    species: species.clone(),
+                    count: state.count,
+                });
+            }
+            if ui.button("Clear All").clicked() {
+                events.clear.send(ClearBoids);
+            }
+        });
+    });
+}
+
+fn spawn_distribution_ui(ui: &mut egui::Ui, distribution: &mut SpawnDistribution) {
+    let label = match distribution {
+        SpawnDistribution::Uniform { .. } => "Uniform",
+        SpawnDistribution::Box { .. } => "Box",
+        SpawnDistribution::Sphere { .. } => "Sphere",
+        SpawnDistribution::Gaussian { .. } => "Gaussian",
+        SpawnDistribution::Mill { .. } => "Mill",
+        SpawnDistribution::School { .. } => "School",
+        SpawnDistribution::File { .. } => "File",
+    };
+    egui::ComboBox::from_label("Distribution")
+        .selected_text(label)
+        .show_ui(ui, |ui| {
+            let options = [
+                ("Uniform", SpawnDistribution::default()),
+                ("Box", SpawnDistribution::Box { min: Vec3::splat(-500.0), max: Vec3::splat(500.0) }),
+                ("Sphere", SpawnDistribution::Sphere { centre: Vec3::ZERO, radius: 500.0 }),
+                ("Gaussian", SpawnDistribution::Gaussian { centre: Vec3::ZERO, std_dev: 200.0 }),
+                ("Mill", SpawnDistribution::Mill { centre: Vec3::ZERO, radius: 400.0, thickness: 100.0 }),
+                ("School", SpawnDistribution::School { centre: Vec3::ZERO, radius: 300.0, heading: Vec3::X }),
+                ("File", SpawnDistribution::File { path: "assets/initial_state.ron".into() }),
+            ];
+            for (name, option) in options {
+                if ui.selectable_label(name == label, name).clicked() && name != label {
+                    *distribution = option;
+                }
+            }
+        });
+    match distribution {
+        SpawnDistribution::Uniform { spacing } => {
+            ui.add(egui::Slider::new(spacing, 1.0..=500.0).text("Spacing"));
+        }
+        SpawnDistribution::Box { min, max } => {
+            vec3_ui(ui, min, "Min");
+            vec3_ui(ui, max, "Max");
+        }
+        SpawnDistribution::Sphere { centre, radius } => {
+            vec3_ui(ui, centre, "Centre");
+            ui.add(egui::Slider::new(radius, 0.0..=3000.0).text("Radius"));
+        }
+        SpawnDistribution::Gaussian { centre, std_dev } => {
+            vec3_ui(ui, centre, "Centre");
+            ui.add(egui::Slider::new(std_dev, 0.0..=2000.0).text("Standard Deviation"));
+        }
+        SpawnDistribution::Mill { centre, radius, thickness } => {
+            vec3_ui(ui, centre, "Centre");
+            ui.add(egui::Slider::new(radius, 0.0..=3000.0).text("Radius"));
+            ui.add(egui::Slider::new(thickness, 0.0..=1000.0).text("Thickness"));
+        }
+        SpawnDistribution::School { centre, radius, heading } => {
+            vec3_ui(ui, centre, "Centre");
+            ui.add(egui::Slider::new(radius, 0.0..=3000.0).text("Radius"));
+            vec3_ui(ui, heading, "Heading");
+        }
+        SpawnDistribution::File { path } => {
+            let mut text = path.to_string_lossy().to_string();
+            if ui.text_edit_singleline(&mut text).changed() {
+                *path = text.into();
+            }
+        }
+    }
+}
+
+fn place_at_cursor(
+    mut contexts: EguiContexts,
+    mut state: ResMut<PopulationGuiState>,
+    manifest: Res<SpeciesManifest>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<camera::Camera>>,
+    mut spawn_events: EventWriter<SpawnBoids>,
+) {
+    if !state.placing || !mouse.just_pressed(MouseButton::Left) || contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single()) else {
+        return;
+    };
+    let (Some(centre), Some(species)) = (camera::cursor_on_ground(window, camera, camera_transform), manifest.species.get(state.species)) else {
+        return;
+    };
+    spawn_events.send(SpawnBoids {
+        species: species.name.clone(),
+        count: state.count,
+        region: SpawnRegion::Around { centre, radius: state.radius },
+    });
+    state.placing = false;
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+
+use bevy::{prelude::*, utils::HashSet};
+use rand::Rng;
+
+use crate::{
+    boid_config::SpeciesConfigs,
+    boid_map::BoidMap,
+    flock::{Boid, Flock, Predator},
+    moveable::{Dimensions, Velocity},
+    obstacle::avoid_obstacles,
+    rng::SimRng,
+    simulation_schedule::{InSimulationSchedule, TransformInterpolation},
+    spawn_distribution::SpawnDistribution,
+    species::SpeciesManifest,
+};
+
+/// What happens to prey once caught
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptureOutcome {
+    // The prey is despawned
+    Remove,
+    // The prey is moved to a new position from the `SpawnDistribution`, keeping the population constant
+    #[default]
+    Respawn,
+}
+
+#[derive(Resource, Debug, Clone, Default)]
+pub struct PredationSettings {
+    pub outcome: CaptureOutcome,
+}
+
+/// Sent when a predator catches a prey, before the prey is removed or respawned
+#[derive(Event, Debug, Clone)]
+pub struct PreyCaptured {
+    pub predator: Entity,
+    pub prey: Entity,
+    pub prey_species: String,
+    // Flock identities of the predator and the prey
+    pub predator_flock: usize,
+    pub prey_flock: usize,
+    pub position: Vec3,
+}
+
+/// Number of prey a predator has caught
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct KillCount(pub u64);
+
+/// Captures since startup, by flock identity
+#[derive(Resource, Debug, Default)]
+pub struct PredationStats {
+    // Prey caught by the predators of each flock
+    pub kills: BTreeMap<usize, u64>,
+    // Members of each flock that were caught
+    pub losses: BTreeMap<usize, u64>,
+}
+
+pub struct PredationPlugin;
+
+impl Plugin for PredationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PredationSettings>()
+            .init_resource::<PredationStats>()
+            .add_event::<PreyCaptured>()
+            .add_systems(FixedUpdate, (capture_prey, count_captures)
+                .chain()
+                .after(avoid_obstacles)
+                .in_set(InSimulationSchedule::EntityUpdates));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn capture_prey(
+    mut commands: Commands,
+    settings: Res<PredationSettings>,
+    configs: Res<SpeciesConfigs>,
+    map: Res<BoidMap>,
+    manifest: Res<SpeciesManifest>,
+    distribution: Res<SpawnDistribution>,
+    dimensions: Res<Dimensions>,
+    mut rng: ResMut<SimRng>,
+    predators: Query<(Entity, &Transform, &Boid, &Flock), With<Predator>>,
+    mut prey: Query<(&mut Transform, &mut TransformInterpolation, &mut Velocity, &Boid, &Flock), Without<Predator>>,
+    mut captured_events: EventWriter<PreyCaptured>,
+    mut captured: Local<HashSet<Entity>>,
+) {
+    captured.clear();
+    for (predator_entity, predator_transform, predator, predator_flock) in predators.iter() {
+        let config = configs.get(&predator.species);
+        if config.capture_radius <= 0.0 || config.capture_probability <= 0.0 {
+            continue;
+        }
+        // each predator makes one attempt per tick, on the closest prey within reach
+        let target = map.within(predator_transform.translation, config.capture_radius)
+            .filter(|entry| !entry.predator && !captured.contains(&entry.entity))
+            .min_by(|a, b| {
+                let (a, b) = (a.position.distance_squared(predator_transform.translation), b.position.distance_squared(predator_transform.translation));
+                a.total_cmp(&b)
+            });
+        let Some(target) = target else {
+            continue;
+        };
+        if rng.gen::<f32>() >= config.capture_probability {
+            continue;
+        }
+        let Ok((mut transform, mut interpolation, mut velocity, prey_boid, prey_flock)) = prey.get_mut(target.entity) else {
+            continue;
+        };
+        captured.insert(target.entity);
+        captured_events.send(PreyCaptured {
+            predator: predator_entity,
+            prey: target.entity,
+            prey_species: prey_boid.species.clone(),
+            predator_flock: predator_flock.identity,
+            prey_flock: prey_flock.identity,
+            position: transform.translation,
+        });
+
+        match settings.outcome {
+            CaptureOutcome::Remove => commands.entity(target.entity).despawn_recursive(),
+            CaptureOutcome::Respawn => {
+                let speed = configs.get(&prey_boid.species).min_speed;
+                let (position, new_velocity) = distribution.sample(&mut rng, manifest.total_count(), speed, *dimensions);
+                transform.translation = position;
+                // appear at the new position instead of sliding there
+                interpolation.previous = *transform;
+                velocity.value = new_velocity;
+            }
+        }
+    }
+}
+
+pub fn count_captures(
+    mut captured_events: EventReader<PreyCaptured>,
+    mut stats: ResMut<PredationStats>,
+    mut predators: Query<&mut KillCount>,
+) {
+    for event in captured_events.read() {
+        *stats.kills.entry(event.predator_flock).or_default() += 1;
+        *stats.losses.entry(event.prey_flock).or_default() += 1;
+        if let Ok(mut kills) = predators.get_mut(event.predator) {
+            kills.0 += 1;
+        }
+    }
+}
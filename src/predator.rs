@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid_config::SpeciesConfigs,
+    boid_map::BoidMap,
+    flock::{apply_flock_centre, bound_vector, predator_prey_rules, Boid, Flock},
+    moveable::{Dimensions, Velocity},
+    simulation_schedule::InSimulationSchedule,
+};
+
+/// Home range of a predator, centred where it was spawned
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Territory {
+    pub centre: Vec3,
+}
+
+pub struct PredatorPlugin;
+
+impl Plugin for PredatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, predator_steering
+            .after(apply_flock_centre)
+            .before(predator_prey_rules)
+            .in_set(InSimulationSchedule::EntityUpdates));
+    }
+}
+
+// How predators steer relative to each other, in place of the boids rules prey follow. Predators
+// keep their distance from predators of any species, stay within their territory and, unless
+// solitary, swim along with the rest of their pack
+pub fn predator_steering(
+    mut predators: Query<(Entity, &Transform, &mut Velocity, &Territory, &Flock, &Boid)>,
+    configs: Res<SpeciesConfigs>,
+    dimensions: Res<Dimensions>,
+    time: Res<Time>,
+    map: Res<BoidMap>,
+) {
+    predators.par_iter_mut().for_each(|(entity, transform, mut velocity, territory, flock, boid)| {
+        let config = configs.get(&boid.species);
+        let position = transform.translation;
+        let mut range = config.predator_spacing_range;
+        if !config.solitary {
+            range = range.max(config.alignment_range).max(config.cohesion_range);
+        }
+
+        let mut total_spacing = Vec3::ZERO;
+        let mut total_alignment = Vec3::ZERO;
+        let mut total_cohesion = Vec3::ZERO;
+        for other in map.within(position, range).filter(|other| other.predator && other.entity != entity) {
+            let distance = position.distance(other.position);
+            if distance < config.predator_spacing_range {
+                total_spacing += (position - other.position).normalize_or_zero();
+            }
+            if config.solitary || other.flock != flock.identity {
+                continue;
+            }
+            if distance < config.alignment_range {
+                total_alignment += other.velocity.normalize_or_zero();
+            }
+            if distance < config.cohesion_range {
+                total_cohesion += (other.position - position).normalize_or_zero();
+            }
+        }
+        let mut force = total_spacing.normalize_or_zero() * config.predator_spacing_strength
+            + total_alignment.normalize_or_zero() * config.alignment_strength
+            + total_cohesion.normalize_or_zero() * config.cohesion_strength;
+
+        if config.territory_radius > 0.0 {
+            let offset = territory.centre - position;
+            let outside = offset.length() - config.territory_radius;
+            if outside > 0.0 {
+                // pulls harder the further out, up to full strength at twice the radius
+                let urgency = (outside / config.territory_radius).min(1.0);
+                force += offset.normalize_or_zero() * config.territory_strength * urgency;
+            }
+        }
+        velocity.value = bound_vector(velocity.value + dimensions.constrain(force) * time.delta_seconds(), config.min_speed, config.max_speed);
+    });
+}
@@ -0,0 +1,102 @@
+use std::{collections::BTreeMap, fmt, fs, io, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{boid_config::{BoidConfig, SpeciesConfigs}, spawn_distribution::SpawnDistribution};
+
+pub const PRESETS_DIR: &str = "assets/presets";
+
+/// Contents of a saved config file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigFile {
+    // Seed the run was started with, used when the file is given on startup
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // Parameters for any species not listed in `species`
+    #[serde(default)]
+    pub boids: BoidConfig,
+    #[serde(default)]
+    pub species: BTreeMap<String, BoidConfig>,
+    // Initial conditions, used when the file is given on startup
+    #[serde(default)]
+    pub spawn: Option<SpawnDistribution>,
+}
+
+#[derive(Debug)]
+pub enum ConfigFileError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigFileError::Io(e) => write!(f, "{}", e),
+            ConfigFileError::Parse(e) => write!(f, "{}", e),
+            ConfigFileError::Serialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigFileError {}
+
+impl ConfigFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let contents = fs::read_to_string(path).map_err(ConfigFileError::Io)?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, ConfigFileError> {
+        ron::from_str(contents).map_err(ConfigFileError::Parse)
+    }
+
+    pub fn from_configs(configs: &SpeciesConfigs, seed: Option<u64>) -> Self {
+        Self {
+            seed,
+            boids: configs.default.clone(),
+            species: configs.species.clone(),
+            spawn: None,
+        }
+    }
+
+    pub fn configs(&self) -> SpeciesConfigs {
+        SpeciesConfigs {
+            default: self.boids.clone(),
+            species: self.species.clone(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigFileError> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(ConfigFileError::Serialize)?;
+        fs::write(path, contents).map_err(ConfigFileError::Io)
+    }
+}
+
+/// Preset files found in [`PRESETS_DIR`] on startup
+#[derive(Resource, Debug, Default)]
+pub struct Presets {
+    pub files: Vec<PathBuf>,
+}
+
+pub struct PresetsPlugin;
+
+impl Plugin for PresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(find_presets());
+    }
+}
+
+fn find_presets() -> Presets {
+    let mut files: Vec<PathBuf> = fs::read_dir(PRESETS_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    Presets { files }
+}
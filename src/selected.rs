@@ -1,9 +1,13 @@
 use bevy::{pbr::wireframe::Wireframe, prelude::*};
-use bevy_mod_picking::{events::{Click, Pointer}, prelude::ListenerInput};
+use bevy_mod_picking::prelude::*;
 
-use crate::{debug::{DebugShape, EntityLink}, utils::get_top_entity};
+use crate::{debug::{DebugShape, EntityLink}, flock::Boid, utils::get_top_entity};
 
 
+/// Marks a boid selected by clicking on it. Clicking it again deselects it
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Selected;
+
 #[derive(Event, Debug, Clone)]
 pub struct SelectedEvent(Entity);
 
@@ -18,7 +22,17 @@ pub struct SelectedPlugin;
 impl Plugin for SelectedPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SelectedEvent>()
-            .add_systems(Update, handle_selected_event);
+            .add_systems(Update, (make_boids_pickable, handle_selected_event));
+    }
+}
+
+fn make_boids_pickable(mut commands: Commands, boids: Query<Entity, Added<Boid>>) {
+    for entity in boids.iter() {
+        commands.entity(entity).insert((
+            PickableBundle::default(),
+            // Creates an event when the entity is clicked
+            On::<Pointer<Click>>::send_event::<SelectedEvent>(),
+        ));
     }
 }
 
@@ -28,11 +42,18 @@ fn handle_selected_event(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     parents: Query<&Parent>,
+    selected: Query<(), With<Selected>>,
+    shapes: Query<(Entity, &EntityLink)>,
 ) {
     for event in selected_events.read() {
         // pointer event seems to get return some entity used for detection, not the actual entity
         // Get the top entity in the hierarchy which has the correct tranform to follow
         let top = get_top_entity(event.0, &parents);
+        if selected.contains(top) {
+            deselect(&mut commands, top, &shapes);
+            continue;
+        }
+        commands.entity(top).insert(Selected);
         commands.spawn((
             DebugShape {
                 linked_to: EntityLink(top),
@@ -46,4 +67,14 @@ fn handle_selected_event(
             Wireframe,
         ));
     }
-}
\ No newline at end of file
+}
+
+/// Removes `Selected` from `entity` along with the shapes highlighting it
+pub fn deselect(commands: &mut Commands, entity: Entity, shapes: &Query<(Entity, &EntityLink)>) {
+    commands.entity(entity).remove::<Selected>();
+    for (shape, link) in shapes.iter() {
+        if link.0 == entity {
+            commands.entity(shape).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    boid_config::SpeciesConfigs,
+    debug::EntityLink,
+    energy::Energy,
+    escape::Panic,
+    flock::{Boid, Flock},
+    moveable::Velocity,
+    predation::KillCount,
+    selected::{deselect, Selected},
+};
+
+pub struct SelectedGuiPlugin;
+
+impl Plugin for SelectedGuiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, selected_egui);
+    }
+}
+
+type SelectedBoid<'a> = (Entity, &'a Boid, &'a Flock, &'a Velocity, Option<&'a Energy>, Option<&'a KillCount>, Option<&'a Panic>);
+
+fn selected_egui(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    configs: Res<SpeciesConfigs>,
+    selected: Query<SelectedBoid, With<Selected>>,
+    shapes: Query<(Entity, &EntityLink)>,
+) {
+    if selected.is_empty() {
+        return;
+    }
+    egui::Window::new("Selected").show(contexts.ctx_mut(), |ui| {
+        for (entity, boid, flock, velocity, energy, kills, panic) in selected.iter() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} in flock {}, speed {:.1}", boid.species, flock.identity, velocity.value.length()));
+                if let Some(kills) = kills {
+                    ui.label(format!("{} kills", kills.0));
+                }
+                if ui.button("Deselect").clicked() {
+                    deselect(&mut commands, entity, &shapes);
+                }
+            });
+            let config = configs.get(&boid.species);
+            if let (Some(energy), Some(energy_config)) = (energy, &config.energy) {
+                let performance = energy.performance(energy_config);
+                ui.add(egui::ProgressBar::new(energy.value)
+                    .text(format!("Energy {:.0}%, max speed {:.1}", energy.value * 100.0, config.max_speed * performance)));
+            }
+            if let Some(panic) = panic.filter(|panic| panic.level > 0.0) {
+                ui.add(egui::ProgressBar::new(panic.level).text(format!("Panic {:.0}%", panic.level * 100.0)));
+            }
+            ui.separator();
+        }
+        if ui.button("Deselect All").clicked() {
+            for (entity, ..) in selected.iter() {
+                deselect(&mut commands, entity, &shapes);
+            }
+        }
+    });
+}
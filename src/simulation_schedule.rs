@@ -1,20 +1,122 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, transform::TransformSystem};
+
+const DEFAULT_TICK_RATE: f64 = 60.0;
 
 #[derive(Hash, Debug, PartialEq, Eq, Clone, SystemSet)]
 pub enum InSimulationSchedule {
+    // Runs in Update, once per frame
     UserInput,
+    // Runs in FixedUpdate, once per simulation tick, gathering what the rules need to know
+    // (spatial map, flock statistics and centres, clusters)
+    Perception,
+    // Runs in FixedUpdate, once per simulation tick
     EntityUpdates,
+    // Integrates velocities once all rules for the tick have been applied
+    Movement,
 }
 
+#[derive(Resource, Debug)]
+pub struct SimulationSettings {
+    // Number of simulation ticks per second, independent of the frame rate
+    pub tick_rate: f64,
+    // Smooth rendered transforms between the last two ticks
+    pub interpolate: bool,
+}
 
-pub struct SimulationSchedulePlugin;
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            tick_rate: DEFAULT_TICK_RATE,
+            interpolate: true,
+        }
+    }
+}
+
+/// Number of fixed simulation ticks that have run since startup
+#[derive(Resource, Debug, Default)]
+pub struct SimulationTick(pub u64);
+
+/// Simulation state of a moving entity at the previous and current tick.
+///
+/// `Transform` is restored from `current` at the start of every tick, so the simulation
+/// never sees the interpolated value written for rendering.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct TransformInterpolation {
+    pub previous: Transform,
+    pub current: Transform,
+}
 
+impl TransformInterpolation {
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            previous: transform,
+            current: transform,
+        }
+    }
+}
+
+pub struct SimulationSchedulePlugin;
 
 impl Plugin for SimulationSchedulePlugin {
     fn build(&self, app: &mut App) {
-        app.configure_sets(Update, (
-            InSimulationSchedule::UserInput,
-            InSimulationSchedule::EntityUpdates,
-        ).chain());
+        app.init_resource::<SimulationSettings>()
+            .init_resource::<SimulationTick>()
+            .insert_resource(Time::<Fixed>::from_hz(DEFAULT_TICK_RATE))
+            .configure_sets(Update, InSimulationSchedule::UserInput)
+            .configure_sets(FixedUpdate, (
+                InSimulationSchedule::Perception,
+                InSimulationSchedule::EntityUpdates,
+                InSimulationSchedule::Movement,
+            ).chain())
+            .add_systems(Update, apply_tick_rate.in_set(InSimulationSchedule::UserInput))
+            .add_systems(FixedUpdate, (
+                restore_simulation_transforms.before(InSimulationSchedule::Perception),
+                (record_simulation_transforms, advance_tick).after(InSimulationSchedule::Movement),
+            ))
+            .add_systems(PostUpdate, interpolate_transforms.before(TransformSystem::TransformPropagate));
     }
-}
\ No newline at end of file
+}
+
+fn apply_tick_rate(settings: Res<SimulationSettings>, mut time: ResMut<Time<Fixed>>) {
+    if !settings.is_changed() || settings.tick_rate <= 0.0 {
+        return;
+    }
+    // Slow frames are caught up by running several ticks in one frame, bounded by the
+    // maximum delta of virtual time so a long stall does not freeze the app
+    time.set_timestep_hz(settings.tick_rate);
+}
+
+fn restore_simulation_transforms(mut query: Query<(&mut Transform, &mut TransformInterpolation)>) {
+    for (mut transform, mut interpolation) in query.iter_mut() {
+        *transform = interpolation.current;
+        interpolation.previous = interpolation.current;
+    }
+}
+
+fn record_simulation_transforms(mut query: Query<(&Transform, &mut TransformInterpolation)>) {
+    for (transform, mut interpolation) in query.iter_mut() {
+        interpolation.current = *transform;
+    }
+}
+
+fn advance_tick(mut tick: ResMut<SimulationTick>) {
+    tick.0 += 1;
+}
+
+fn interpolate_transforms(
+    settings: Res<SimulationSettings>,
+    time: Res<Time<Fixed>>,
+    mut query: Query<(&mut Transform, &TransformInterpolation)>,
+) {
+    let s = time.overstep_percentage().clamp(0.0, 1.0);
+    for (mut transform, interpolation) in query.iter_mut() {
+        if !settings.interpolate {
+            *transform = interpolation.current;
+            continue;
+        }
+        let (previous, current) = (interpolation.previous, interpolation.current);
+        transform.translation = previous.translation.lerp(current.translation, s);
+        transform.rotation = previous.rotation.slerp(current.rotation, s);
+        transform.scale = previous.scale.lerp(current.scale, s);
+    }
+}
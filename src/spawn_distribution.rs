@@ -0,0 +1,135 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{moveable::Dimensions, presets::ConfigFileError, rng::SimRng};
+
+/// How boids are placed and set moving when the species manifest is spawned
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SpawnDistribution {
+    // Scattered over a square (2D) or cube (3D) around the origin, sized so boids start about `spacing` apart
+    Uniform { spacing: f32 },
+    // Scattered over an axis aligned box
+    Box { min: Vec3, max: Vec3 },
+    // Scattered over a ball (3D) or disc (2D)
+    Sphere { centre: Vec3, radius: f32 },
+    // Normally distributed around a point
+    Gaussian { centre: Vec3, std_dev: f32 },
+    // Circling around a horizontal ring, all the same way round, within `thickness` of it, as in a fish mill
+    Mill { centre: Vec3, radius: f32, thickness: f32 },
+    // Within a ball, all heading the same way
+    School { centre: Vec3, radius: f32, heading: Vec3 },
+    // Positions and velocities of every boid, as saved by `InitialState::save`. Species counts in
+    // the manifest are ignored
+    File { path: PathBuf },
+}
+
+impl Default for SpawnDistribution {
+    fn default() -> Self {
+        SpawnDistribution::Uniform { spacing: 100.0 }
+    }
+}
+
+impl SpawnDistribution {
+    /// Position and velocity of a boid, `total` being the number of boids spawned together.
+    /// A file has nothing to sample from, so `File` samples the default distribution instead
+    pub fn sample(&self, rng: &mut SimRng, total: usize, speed: f32, dimensions: Dimensions) -> (Vec3, Vec3) {
+        let (position, velocity) = match self {
+            SpawnDistribution::Uniform { spacing } => {
+                let size = match dimensions {
+                    Dimensions::Two => spacing * (total as f32).sqrt(),
+                    Dimensions::Three => spacing * (total as f32).cbrt(),
+                };
+                let position = (Vec3::new(rng.gen(), rng.gen(), rng.gen()) - 0.5) * size;
+                (position, random_direction(rng, dimensions) * speed)
+            }
+            SpawnDistribution::Box { min, max } => {
+                let position = *min + Vec3::new(rng.gen(), rng.gen(), rng.gen()) * (*max - *min);
+                (position, random_direction(rng, dimensions) * speed)
+            }
+            SpawnDistribution::Sphere { centre, radius } => {
+                (*centre + random_in_ball(rng, dimensions) * *radius, random_direction(rng, dimensions) * speed)
+            }
+            SpawnDistribution::Gaussian { centre, std_dev } => {
+                let offset = Vec3::new(gaussian(rng), gaussian(rng), gaussian(rng)) * *std_dev;
+                (*centre + offset, random_direction(rng, dimensions) * speed)
+            }
+            SpawnDistribution::Mill { centre, radius, thickness } => {
+                let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+                let around = Vec3::new(angle.cos(), 0.0, angle.sin());
+                // spread over the cross section of the torus, which is a line across the ring in 2D
+                let cross_section = random_in_ball(rng, Dimensions::Three) * *thickness;
+                let position = *centre + around * (*radius + cross_section.x) + Vec3::Y * cross_section.y;
+                (position, Vec3::new(around.z, 0.0, -around.x) * speed)
+            }
+            SpawnDistribution::School { centre, radius, heading } => {
+                let heading = heading.try_normalize().unwrap_or(Vec3::X);
+                (*centre + random_in_ball(rng, dimensions) * *radius, heading * speed)
+            }
+            SpawnDistribution::File { .. } => {
+                return SpawnDistribution::default().sample(rng, total, speed, dimensions);
+            }
+        };
+        (dimensions.constrain(position), dimensions.constrain(velocity))
+    }
+}
+
+/// Uniformly distributed direction, horizontal in 2D
+pub fn random_direction(rng: &mut SimRng, dimensions: Dimensions) -> Vec3 {
+    match dimensions {
+        Dimensions::Two => {
+            let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+            Vec3::new(angle.cos(), 0.0, angle.sin())
+        }
+        // a vector of independent normal components points in every direction with equal probability
+        Dimensions::Three => Vec3::new(gaussian(rng), gaussian(rng), gaussian(rng)).try_normalize().unwrap_or(Vec3::X),
+    }
+}
+
+// Uniformly distributed within the unit ball, or the unit disc in 2D
+fn random_in_ball(rng: &mut SimRng, dimensions: Dimensions) -> Vec3 {
+    loop {
+        let point = dimensions.constrain(Vec3::new(rng.gen(), rng.gen(), rng.gen()) * 2.0 - 1.0);
+        if point.length_squared() <= 1.0 {
+            return point;
+        }
+    }
+}
+
+// Standard normal sample, by the Box-Muller transform
+fn gaussian(rng: &mut SimRng) -> f32 {
+    let u1 = rng.gen::<f32>().max(f32::MIN_POSITIVE);
+    let u2 = rng.gen::<f32>();
+    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
+
+/// A boid in an initial state file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InitialBoid {
+    pub species: String,
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+/// Contents of a file for [`SpawnDistribution::File`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InitialState {
+    // Seed of the run the state was saved from, used when the file is spawned from on startup
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub boids: Vec<InitialBoid>,
+}
+
+impl InitialState {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let contents = fs::read_to_string(path).map_err(ConfigFileError::Io)?;
+        ron::from_str(&contents).map_err(ConfigFileError::Parse)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigFileError> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(ConfigFileError::Serialize)?;
+        fs::write(path, contents).map_err(ConfigFileError::Io)
+    }
+}
@@ -0,0 +1,109 @@
+use std::{fs, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boid_config::{BoidConfig, SpeciesConfigs},
+    presets::ConfigFileError,
+};
+
+pub const DEFAULT_MANIFEST_PATH: &str = "assets/species.ron";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Role {
+    #[default]
+    Prey,
+    Predator,
+}
+
+/// A species that can be spawned, as listed in the species manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeciesDefinition {
+    pub name: String,
+    // Path of the glTF model, relative to the assets folder
+    pub model: String,
+    // Index of the swim animation within the model
+    #[serde(default)]
+    pub animation: usize,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    // Number spawned on startup
+    pub count: usize,
+    #[serde(default)]
+    pub role: Role,
+    // Behaviour parameters, the default parameters are used when not given
+    #[serde(default)]
+    pub behaviour: Option<BoidConfig>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+/// All species in the simulation. Each species forms its own flock, identified by its index
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct SpeciesManifest {
+    pub species: Vec<SpeciesDefinition>,
+}
+
+impl Default for SpeciesManifest {
+    // Used when no manifest file can be found
+    fn default() -> Self {
+        Self {
+            species: vec![
+                SpeciesDefinition {
+                    name: "Fish".to_string(),
+                    model: "Fish.glb".to_string(),
+                    animation: 0,
+                    scale: 1.0,
+                    count: 1000,
+                    role: Role::Prey,
+                    behaviour: None,
+                },
+                SpeciesDefinition {
+                    name: "Shark".to_string(),
+                    model: "Shark.glb".to_string(),
+                    animation: 0,
+                    scale: 1.0,
+                    count: 5,
+                    role: Role::Predator,
+                    behaviour: None,
+                },
+            ],
+        }
+    }
+}
+
+impl SpeciesManifest {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let contents = fs::read_to_string(path).map_err(ConfigFileError::Io)?;
+        ron::from_str(&contents).map_err(ConfigFileError::Parse)
+    }
+
+    // Loads the manifest shipped in the assets folder, falling back to the built in species
+    pub fn load_default() -> Self {
+        Self::load(DEFAULT_MANIFEST_PATH).unwrap_or_else(|e| {
+            warn!("Could not load {}, using built in species: {}", DEFAULT_MANIFEST_PATH, e);
+            Self::default()
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SpeciesDefinition> {
+        self.species.iter().find(|species| species.name == name)
+    }
+
+    pub fn total_count(&self) -> usize {
+        self.species.iter().map(|species| species.count).sum()
+    }
+
+    // Behaviour parameters of every species which defines its own
+    pub fn configs(&self) -> SpeciesConfigs {
+        SpeciesConfigs {
+            default: BoidConfig::default(),
+            species: self.species.iter()
+                .filter_map(|species| species.behaviour.clone().map(|config| (species.name.clone(), config)))
+                .collect(),
+        }
+    }
+}